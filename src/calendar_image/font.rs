@@ -0,0 +1,250 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use svg::node::{
+	element::{Path, Text},
+	Node, Value,
+};
+use ttf_parser::{name_id, Face, GlyphId, OutlineBuilder};
+
+use super::str_to_charcount;
+
+/// How text is measured and drawn.
+///
+/// Without a font file, each character is counted as half as wide as it is tall, and it's up to
+/// whatever shows the image to pick a font.
+pub struct Lettering<'a> {
+	family: Option<String>,
+	face: Option<Face<'a>>,
+	/// Whether to draw text as the outlines of its letters, so it looks the same everywhere
+	outlines: bool,
+}
+
+impl<'a> Lettering<'a> {
+	/// Read a font from the contents of a font file, if there is one. The font's own name is used
+	/// when no family is given.
+	pub fn new(family: Option<&str>, font_data: Option<&'a [u8]>, outlines: bool) -> Result<Self> {
+		let face = font_data
+			.map(|data| Face::parse(data, 0))
+			.transpose()
+			.context("Could not read the font file")?;
+		let family = family.map(ToOwned::to_owned).or_else(|| {
+			face.as_ref()?
+				.names()
+				.into_iter()
+				.filter(|name| name.name_id == name_id::FAMILY)
+				.find_map(|name| name.to_string())
+		});
+		Ok(Self {
+			family,
+			face,
+			outlines,
+		})
+	}
+
+	/// Glyphs of some text, with how far along the line each one starts, in font units.
+	fn glyphs(face: &Face, text: &str) -> Vec<(GlyphId, f64)> {
+		let mut advance = 0.0;
+		text.chars()
+			.map(|c| {
+				let glyph = face.glyph_index(c).unwrap_or(GlyphId(0));
+				let start = advance;
+				advance += f64::from(face.glyph_hor_advance(glyph).unwrap_or_default());
+				(glyph, start)
+			})
+			.collect()
+	}
+
+	/// How wide some text is at a font size, leaving out kerning.
+	pub fn width(&self, text: &str, size: f64) -> Result<f64> {
+		Ok(match self.face {
+			Some(ref face) => {
+				let width = text
+					.chars()
+					.map(|c| face.glyph_index(c).unwrap_or(GlyphId(0)))
+					.map(|glyph| f64::from(face.glyph_hor_advance(glyph).unwrap_or_default()))
+					.sum::<f64>();
+				width * size / f64::from(face.units_per_em())
+			},
+			None => size * f64::from(str_to_charcount(text)?) / 2.0,
+		})
+	}
+
+	/// Some text lined up on a point by `anchor`, like `text-anchor` in SVG, or starting at it
+	/// without one. Text is centered on `y` when `central` is set, and sits on it otherwise.
+	pub fn text(
+		&self,
+		content: &str,
+		(x, y): (f64, f64),
+		size: f64,
+		anchor: Option<&str>,
+		central: bool,
+	) -> Result<Label> {
+		if let (true, Some(face)) = (self.outlines, &self.face) {
+			let scale = size / f64::from(face.units_per_em());
+			let left = match anchor {
+				Some("middle") => x - self.width(content, size)? / 2.0,
+				Some("end") => x - self.width(content, size)?,
+				_ => x,
+			};
+			let baseline = if central {
+				(f64::from(face.ascender()) + f64::from(face.descender())).mul_add(scale / 2.0, y)
+			} else {
+				y
+			};
+			let mut outline = Outline {
+				d: String::new(),
+				origin: (left, baseline),
+				scale,
+			};
+			for (glyph, start) in Self::glyphs(face, content) {
+				outline.origin.0 = start.mul_add(scale, left);
+				face.outline_glyph(glyph, &mut outline);
+			}
+			return Ok(Label::Path(
+				Path::new().set("d", outline.d.trim_end().to_owned()),
+			));
+		}
+		let mut text = Text::new(content)
+			.set("x", x)
+			.set("y", y)
+			.set("font-size", format!("{size}px"));
+		if let Some(ref family) = self.family {
+			text = text.set("font-family", family.as_str());
+		}
+		if let Some(anchor) = anchor {
+			text = text.set("text-anchor", anchor);
+		}
+		if central {
+			text = text.set("dominant-baseline", "central");
+		}
+		Ok(Label::Text(text))
+	}
+}
+
+/// Text, or the outlines of its letters.
+#[derive(Debug, Clone)]
+pub enum Label {
+	Text(Text),
+	Path(Path),
+}
+
+impl Label {
+	pub fn set<T: Into<String>, U: Into<Value>>(self, name: T, value: U) -> Self {
+		match self {
+			Self::Text(text) => Self::Text(text.set(name, value)),
+			Self::Path(path) => Self::Path(path.set(name, value)),
+		}
+	}
+}
+
+impl From<Label> for Box<dyn Node> {
+	fn from(label: Label) -> Self {
+		match label {
+			Label::Text(text) => text.into(),
+			Label::Path(path) => path.into(),
+		}
+	}
+}
+
+/// Writes the outline of a glyph as path data, flipped the right way up for SVG.
+struct Outline {
+	d: String,
+	/// Where the glyph's origin goes in the image
+	origin: (f64, f64),
+	scale: f64,
+}
+
+impl Outline {
+	fn point(&self, x: f32, y: f32) -> (f64, f64) {
+		(
+			f64::from(x).mul_add(self.scale, self.origin.0),
+			f64::from(-y).mul_add(self.scale, self.origin.1),
+		)
+	}
+}
+
+impl OutlineBuilder for Outline {
+	fn move_to(&mut self, x: f32, y: f32) {
+		let (x, y) = self.point(x, y);
+		let _ = write!(self.d, "M {x} {y} ");
+	}
+
+	fn line_to(&mut self, x: f32, y: f32) {
+		let (x, y) = self.point(x, y);
+		let _ = write!(self.d, "L {x} {y} ");
+	}
+
+	fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+		let ((x1, y1), (x, y)) = (self.point(x1, y1), self.point(x, y));
+		let _ = write!(self.d, "Q {x1} {y1} {x} {y} ");
+	}
+
+	fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+		let ((x1, y1), (x2, y2), (x, y)) =
+			(self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+		let _ = write!(self.d, "C {x1} {y1} {x2} {y2} {x} {y} ");
+	}
+
+	fn close(&mut self) {
+		self.d.push_str("Z ");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn without_a_font_file_text_is_left_to_the_viewer() {
+		let lettering = Lettering::new(Some("serif"), None, true).unwrap();
+		assert!((lettering.width("You Are Here", 24.0).unwrap() - 144.0).abs() < 1e-9);
+		let Label::Text(text) = lettering
+			.text("80", (10.0, 20.0), 24.0, Some("end"), true)
+			.unwrap()
+		else {
+			panic!("Text can't be turned into paths without a font");
+		};
+		let text = text.to_string();
+		assert!(text.contains("font-family=\"serif\""));
+		assert!(text.contains("text-anchor=\"end\""));
+		assert!(text.contains("dominant-baseline=\"central\""));
+	}
+
+	/// Tuffy, which is in the public domain.
+	const FONT: &[u8] = include_bytes!("../../tests/fixtures/Tuffy.ttf");
+
+	#[test]
+	fn font_files_give_real_widths() {
+		let lettering = Lettering::new(None, Some(FONT), false).unwrap();
+		assert_eq!(lettering.family.as_deref(), Some("Tuffy"));
+		let wide = lettering.width("W", 24.0).unwrap();
+		let narrow = lettering.width("i", 24.0).unwrap();
+		assert!(wide > narrow * 2.0, "W is {wide} wide and i is {narrow}");
+		assert!(
+			(lettering.width("Wi", 24.0).unwrap() - (wide + narrow)).abs() < 1e-9,
+			"Widths add up"
+		);
+	}
+
+	#[test]
+	fn text_can_be_drawn_as_outlines_lined_up_by_its_anchor() {
+		let lettering = Lettering::new(None, Some(FONT), true).unwrap();
+		let outline = |x: f64, anchor: Option<&str>| {
+			let Label::Path(path) = lettering
+				.text("Hi", (x, 20.0), 24.0, anchor, false)
+				.unwrap()
+			else {
+				panic!("Text should be drawn as paths with a font");
+			};
+			path.to_string()
+		};
+		let width = lettering.width("Hi", 24.0).unwrap();
+		let start = outline(0.0, None);
+		assert!(start.starts_with("<path d=\"M "), "{start}");
+		assert!(!start.contains("<text"));
+		assert_eq!(outline(width / 2.0, Some("middle")), start);
+		assert_eq!(outline(width, Some("end")), start);
+		assert_ne!(outline(width, None), start);
+	}
+}
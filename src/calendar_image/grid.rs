@@ -1,20 +1,34 @@
-use std::str::FromStr;
+use std::{f64::consts::TAU, fs};
 
-use anyhow::Result;
-use death_calendar::death_day;
-use gregorian::Date;
+use anyhow::{bail, Context, Result};
+use death_calendar::{death_day, years_lived};
+use gregorian::{Date, DateResultExt, Year};
 use serde::{Deserialize, Serialize};
 use svg::{
-	node::element::{Circle, Element, Rectangle},
+	node::element::{
+		tag::{self, Type},
+		Circle, ClipPath, Definitions, Element, Group, Path, Rectangle, Symbol, Text, Title, Use,
+	},
+	parser::Event,
 	Document, Node,
 };
 
-use super::{init_document, WEEKS_IN_A_YEAR};
-use crate::{DrawingInfoValidated, GridRatios, LifeInfo};
+use super::{
+	init_document, num_to_charcount,
+	pattern::{Pattern, Patterns},
+	pixels, Canvas, StyleClasses, WEEKS_IN_A_YEAR,
+};
+use crate::{
+	death_info::group_digits, phases, DrawingInfoValidated, GridLabels, GridLayout, GridRatios,
+	GridStyle, LifeInfo,
+};
 
 #[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BorderUnit {
+	#[value(alias = "0", alias = "pixels")]
 	Pixel,
+	#[value(alias = "1", alias = "shapes")]
 	Shape,
 }
 
@@ -31,44 +45,713 @@ impl std::fmt::Display for BorderUnit {
 	}
 }
 
-#[derive(Debug)]
-pub struct ParseBorderUnitError;
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SvgShape {
+	Square,
+	Circle,
+	/// Hexagons, with every other line shifted so they fit together like a honeycomb
+	Hexagon,
+	/// Squares with rounded corners, see `--corner-radius`
+	RoundedSquare,
+	Diamond,
+	Heart,
+}
 
-impl std::fmt::Display for ParseBorderUnitError {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(
-			f,
-			"Could not parse string as a border unit variant\nDid you misspell it?"
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GridUnit {
+	Day,
+	Week,
+	Month,
+	Quarter,
+	Year,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Orientation {
+	/// Each line of the grid is a column, so years go across the image
+	Columns,
+	/// Each line of the grid is a row, so years go down the image
+	Rows,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Alignment {
+	/// Squeeze every year into the same number of shapes, so each line starts on a birthday
+	Compressed,
+	/// Each line runs from one birthday to the next, counting real weeks
+	Birthday,
+	/// Each line runs from the 1st of January to the 31st of December, counting real weeks
+	Calendar,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Weekday {
+	Monday,
+	Tuesday,
+	Wednesday,
+	Thursday,
+	Friday,
+	Saturday,
+	Sunday,
+}
+
+impl Weekday {
+	/// Days since the most recent Monday.
+	const fn number(&self) -> i32 {
+		match *self {
+			Self::Monday => 0,
+			Self::Tuesday => 1,
+			Self::Wednesday => 2,
+			Self::Thursday => 3,
+			Self::Friday => 4,
+			Self::Saturday => 5,
+			Self::Sunday => 6,
+		}
+	}
+
+	/// Go back to the closest day on or before a date that falls on this weekday.
+	const fn on_or_before(&self, date: Date) -> Date {
+		// The 1st of January of the year 0 was a Saturday.
+		let weekday_of_date = (date.days_since_year_zero() + 5).rem_euclid(7);
+		date.sub_days((weekday_of_date - self.number()).rem_euclid(7))
+	}
+}
+
+impl GridUnit {
+	/// How many shapes make up a year.
+	const fn cells_per_year(&self) -> u32 {
+		match *self {
+			Self::Day => 365,
+			Self::Week => WEEKS_IN_A_YEAR,
+			Self::Month => 12,
+			Self::Quarter => 4,
+			Self::Year => 1,
+		}
+	}
+
+	/// What one shape is called.
+	const fn name(&self) -> &'static str {
+		match *self {
+			Self::Day => "Day",
+			Self::Week => "Week",
+			Self::Month => "Month",
+			Self::Quarter => "Quarter",
+			Self::Year => "Year",
+		}
+	}
+
+	/// How many shapes go in each line of the grid unless told otherwise.
+	const fn line_length(&self) -> u32 {
+		match *self {
+			Self::Year => 10,
+			_ => self.cells_per_year(),
+		}
+	}
+}
+
+/// A single shape in the grid and the span of time it stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+	/// The first day in the cell
+	pub start: Date,
+	/// The first day after the cell
+	pub end: Date,
+}
+
+/// Advance a day at a time, skipping the 29th of February whenever it comes.
+///
+/// This keeps every year at 365 days, so each line of a grid starts on a birthday.
+fn next_day_skipping_leap_day(date: Date) -> Date {
+	let next = date.next();
+	if next.month() == 2 && next.day() == 29 {
+		next.next()
+	} else {
+		next
+	}
+}
+
+/// Split a life into the cells of a grid.
+pub fn cells(unit: &GridUnit, bday: Date, end: Date) -> Vec<Cell> {
+	let mut cells = Vec::new();
+	let mut curr_date = bday;
+	let mut count: u32 = 0;
+	while curr_date < end {
+		let next_date = match *unit {
+			GridUnit::Day => next_day_skipping_leap_day(curr_date),
+			GridUnit::Week => {
+				// All this below is just to make sure there are always 52 weeks. To do this, we
+				// change the number of days in a week and skip the 29th of February whenever it
+				// comes.
+				let week_length = if count.is_multiple_of(WEEKS_IN_A_YEAR) {
+					8
+				} else {
+					7
+				};
+				(0_u8..week_length).fold(curr_date, |date, _| next_day_skipping_leap_day(date))
+			},
+			GridUnit::Month => bday
+				.add_months(i32::try_from(count + 1).unwrap_or(i32::MAX))
+				.or_prev_valid(),
+			GridUnit::Quarter => bday
+				.add_months(i32::try_from((count + 1) * 3).unwrap_or(i32::MAX))
+				.or_prev_valid(),
+			GridUnit::Year => bday
+				.year_month()
+				.add_years(i16::try_from(count + 1).unwrap_or(i16::MAX))
+				.with_day(bday.day())
+				.or_prev_valid(),
+		};
+		cells.push(Cell {
+			start: curr_date,
+			end: next_date.min(end),
+		});
+		curr_date = next_date;
+		count += 1;
+	}
+	cells
+}
+
+/// A line of the grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+	/// Position of the first cell along the line, which is only ever more than 0 if the line
+	/// starts before you were born
+	pub first_pos: u32,
+	pub cells: Vec<Cell>,
+}
+
+/// Start of the `n`th year of a life, either on a birthday or on the 1st of January.
+fn year_start(alignment: &Alignment, bday: Date, n: i16) -> Date {
+	match *alignment {
+		Alignment::Calendar => Year::new(bday.year().to_number() + n).first_day(),
+		_ => bday
+			.year_month()
+			.add_years(n)
+			.with_day(bday.day())
+			.or_prev_valid(),
+	}
+}
+
+/// Start of the `n`th month of the `year`th year of a life.
+fn month_start(alignment: &Alignment, bday: Date, year: i16, n: i32) -> Date {
+	let first = year_start(alignment, bday, year);
+	match *alignment {
+		Alignment::Calendar => first.year_month().add_months(n).first_day(),
+		_ => first
+			.year_month()
+			.add_months(n)
+			.with_day(bday.day())
+			.or_prev_valid(),
+	}
+}
+
+/// Split a life into lines, with each line being a real year.
+///
+/// Unlike [`cells`], every cell is an exact span of the calendar, so years can have 53 weeks or
+/// 366 days. Weeks start on `week_start`, and belong to whichever year has their fourth day, the
+/// same way as ISO weeks.
+pub fn aligned_lines(
+	unit: &GridUnit,
+	alignment: &Alignment,
+	week_start: &Weekday,
+	bday: Date,
+	end: Date,
+) -> Vec<Line> {
+	// The first week of a year can start a few days before the year itself.
+	let line_start = |year: i16| {
+		let start = year_start(alignment, bday, year);
+		match *unit {
+			GridUnit::Week => week_start.on_or_before(start.add_days(3)),
+			_ => start,
+		}
+	};
+	let mut lines = Vec::new();
+	let mut year: i16 = 0;
+	while line_start(year) < end {
+		let this_year = year_start(alignment, bday, year);
+		let next_year = year_start(alignment, bday, year + 1);
+		let mut starts: Vec<Date> = match *unit {
+			GridUnit::Day => (0..Date::days_since(this_year, next_year))
+				.map(|day| this_year.add_days(day))
+				.collect(),
+			GridUnit::Week => {
+				let first = line_start(year);
+				(0..Date::days_since(first, line_start(year + 1)) / 7)
+					.map(|week| first.add_days(week * 7))
+					.collect()
+			},
+			GridUnit::Month => (0..12)
+				.map(|month| month_start(alignment, bday, year, month))
+				.collect(),
+			GridUnit::Quarter => (0..4)
+				.map(|quarter| month_start(alignment, bday, year, quarter * 3))
+				.collect(),
+			GridUnit::Year => vec![this_year],
+		};
+		starts.push(line_start(year + 1));
+
+		let mut line = Line {
+			first_pos: 0,
+			cells: Vec::new(),
+		};
+		for (pos, span) in (0_u32..).zip(starts.windows(2)) {
+			let cell = Cell {
+				start: span[0].max(bday),
+				end: span[1].min(end),
+			};
+			if cell.start >= cell.end {
+				continue;
+			}
+			if line.cells.is_empty() {
+				line.first_pos = pos;
+			}
+			line.cells.push(cell);
+		}
+		// A life can start too late in a year to have any of its weeks.
+		if !line.cells.is_empty() {
+			lines.push(line);
+		}
+		year += 1;
+	}
+	lines
+}
+
+/// Where each shape of the grid goes, counting the gutters between groups of shapes.
+struct Spacing<'a> {
+	orientation: &'a Orientation,
+	/// Width and height taken up by one shape and the padding around it
+	slot_size: u32,
+	/// Distance from one line to the next, which is less than a slot when lines nestle together
+	line_pitch: u32,
+	/// How far every other line is shifted along its length
+	stagger: u32,
+	/// Width of each gutter
+	gutter: u32,
+	line_length: u32,
+	/// How many shapes' worth of time each line covers
+	line_span: u32,
+	cells_per_year: u32,
+	/// Put a gutter between lines every this many years, or never if it is 0
+	year_gutter_interval: u32,
+	/// Put a gutter along each line every this many shapes
+	cell_gutter_interval: Option<u32>,
+}
+
+impl Spacing<'_> {
+	/// How old you are at the start of a line.
+	const fn age_at_line(&self, line: u32) -> u32 {
+		line * self.line_span / self.cells_per_year
+	}
+
+	/// Distance from the first line to the start of a line.
+	fn line_offset(&self, line: u32) -> u32 {
+		let gutters = self
+			.age_at_line(line)
+			.checked_div(self.year_gutter_interval)
+			.unwrap_or(0);
+		line * self.line_pitch + gutters * self.gutter
+	}
+
+	/// Distance from the start of a line to a position along it.
+	fn pos_offset(&self, pos: u32) -> u32 {
+		let gutters = self
+			.cell_gutter_interval
+			.map_or(0, |interval| pos / interval);
+		pos * self.slot_size + gutters * self.gutter
+	}
+
+	/// Size of the grid across all of its lines.
+	///
+	/// This is worked out without overflowing, so the offsets of every line inside it can't.
+	fn lines_size(&self, line_count: u32) -> Result<u32> {
+		let line = line_count.saturating_sub(1);
+		let gutters = self
+			.age_at_line(line)
+			.checked_div(self.year_gutter_interval)
+			.unwrap_or(0);
+		pixels(
+			f64::from(line).mul_add(
+				f64::from(self.line_pitch),
+				f64::from(gutters) * f64::from(self.gutter),
+			) + f64::from(self.slot_size),
 		)
 	}
+
+	/// Size of the grid along one line.
+	fn line_size(&self) -> Result<u32> {
+		let pos = self.line_length.saturating_sub(1);
+		let gutters = self
+			.cell_gutter_interval
+			.map_or(0, |interval| pos / interval);
+		pixels(
+			f64::from(pos).mul_add(
+				f64::from(self.slot_size),
+				f64::from(gutters) * f64::from(self.gutter),
+			) + f64::from(self.slot_size)
+				+ f64::from(self.stagger),
+		)
+	}
+
+	/// Top left corner of a slot, relative to the top left corner of the grid.
+	fn slot(&self, line: u32, pos: u32) -> (u32, u32) {
+		let stagger = if line % 2 == 1 { self.stagger } else { 0 };
+		let (line_offset, pos_offset) = (self.line_offset(line), self.pos_offset(pos) + stagger);
+		match *self.orientation {
+			Orientation::Columns => (line_offset, pos_offset),
+			Orientation::Rows => (pos_offset, line_offset),
+		}
+	}
+}
+
+/// Where a shape goes in the image.
+struct Place {
+	/// Left edge of a square
+	x: u32,
+	/// Top edge of a square
+	y: u32,
+	/// Horizontal center of a circle
+	cx: u32,
+	/// Vertical center of a circle
+	cy: u32,
+	/// Width and height of the shape
+	size: u32,
+}
+
+/// Everything needed to draw the shape of a cell.
+struct CellShape<'a> {
+	shape: &'a SvgShape,
+	/// Radius of the corners of a rounded square
+	corner_radius: u32,
+	/// Hexagons point along the lines of the grid, so neighbouring lines fit together.
+	orientation: &'a Orientation,
+	/// Draw every cell with the `<symbol>` loaded from the user's file instead
+	use_symbol: bool,
 }
 
-impl FromStr for BorderUnit {
-	type Err = ParseBorderUnitError;
+/// The id of the `<symbol>` holding the user's own shape.
+const SYMBOL_ID: &str = "week-shape";
+
+/// A heart drawn in a 1 by 1 box, as the control points and end point of each of its curves.
+const HEART: [[(f64, f64); 3]; 6] = [
+	[(0.2, 0.7), (0.0, 0.5), (0.0, 0.3)],
+	[(0.0, 0.1), (0.15, 0.0), (0.28, 0.0)],
+	[(0.4, 0.0), (0.5, 0.1), (0.5, 0.2)],
+	[(0.5, 0.1), (0.6, 0.0), (0.72, 0.0)],
+	[(0.85, 0.0), (1.0, 0.1), (1.0, 0.3)],
+	[(1.0, 0.5), (0.8, 0.7), (0.5, 0.95)],
+];
+
+/// Outline of a shape made of straight lines or curves, as the `d` attribute of a path.
+fn shape_outline(shape: &CellShape, place: &Place) -> String {
+	let (x, y, size) = (
+		f64::from(place.x),
+		f64::from(place.y),
+		f64::from(place.size),
+	);
+	let (cx, cy) = (f64::from(place.cx), f64::from(place.cy));
+	let point = |(u, v): (f64, f64)| format!("{} {}", u.mul_add(size, x), v.mul_add(size, y));
+	match *shape.shape {
+		SvgShape::Diamond => format!(
+			"M {cx} {y} L {} {cy} L {cx} {} L {x} {cy} Z",
+			x + size,
+			y + size
+		),
+		SvgShape::Hexagon => {
+			// The flat sides are one shape apart, so the corners are a little further out.
+			let radius = size / 3_f64.sqrt();
+			let corners = (0..6)
+				.map(|corner| {
+					let angle = f64::from(corner) * TAU / 6.0;
+					let (along, across) = (radius * angle.cos(), radius * angle.sin());
+					match *shape.orientation {
+						Orientation::Columns => format!("{} {}", cx + along, cy + across),
+						Orientation::Rows => format!("{} {}", cx + across, cy + along),
+					}
+				})
+				.collect::<Vec<_>>();
+			format!("M {} Z", corners.join(" L "))
+		},
+		SvgShape::Heart => {
+			let curves = HEART
+				.iter()
+				.map(|curve| format!("C {}", curve.map(point).join(" ")))
+				.collect::<Vec<_>>();
+			format!("M {} {} Z", point((0.5, 0.95)), curves.join(" "))
+		},
+		SvgShape::Square | SvgShape::RoundedSquare | SvgShape::Circle => {
+			format!("M {x} {y} H {} V {} H {x} Z", x + size, y + size)
+		},
+	}
+}
+
+/// A shape without any colors, so it can take them from whatever uses it.
+fn make_outline(shape: &CellShape, place: &Place) -> Element {
+	if shape.use_symbol {
+		return Use::new()
+			.set("href", format!("#{SYMBOL_ID}"))
+			.set("x", place.x)
+			.set("y", place.y)
+			.set("width", place.size)
+			.set("height", place.size)
+			.into();
+	}
+	match *shape.shape {
+		SvgShape::Square | SvgShape::RoundedSquare => {
+			let mut square = Rectangle::new()
+				.set("x", place.x)
+				.set("y", place.y)
+				.set("width", place.size)
+				.set("height", place.size);
+			if let SvgShape::RoundedSquare = *shape.shape {
+				square = square.set("rx", shape.corner_radius.min(place.size / 2));
+			}
+			square.into()
+		},
+		SvgShape::Circle => Circle::new()
+			.set("cx", place.cx)
+			.set("cy", place.cy)
+			.set("r", place.size / 2)
+			.into(),
+		SvgShape::Hexagon | SvgShape::Diamond | SvgShape::Heart => {
+			Path::new().set("d", shape_outline(shape, place)).into()
+		},
+	}
+}
+
+fn make_shape(
+	shape: &CellShape,
+	place: &Place,
+	fill: &str,
+	stroke: &str,
+	stroke_width: u32,
+) -> Element {
+	let mut element = make_outline(shape, place);
+	element.assign("fill", fill);
+	element.assign("stroke", stroke);
+	element.assign("stroke-width", stroke_width);
+	element
+}
 
-	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		match s.to_lowercase().as_str() {
-			"0" | "pixel" | "pixels" => Ok(Self::Pixel),
-			"1" | "shape" | "shapes" => Ok(Self::Shape),
-			_ => Err(ParseBorderUnitError),
+/// The id of the shape that compact images reuse for every cell.
+const CELL_ID: &str = "cell";
+
+/// A cell that reuses the shape defined once with the id [`CELL_ID`], colored by a CSS class.
+fn make_compact_shape(place: &Place, class: &str) -> Use {
+	Use::new()
+		.set("href", format!("#{CELL_ID}"))
+		.set("x", place.x)
+		.set("y", place.y)
+		.set("class", class)
+}
+
+/// Fill in part of a shape to show how much of it has passed so far.
+///
+/// Circles fill up clockwise like a pie chart. Every other shape fills up from the left like a
+/// bar, cut to the outline of the shape.
+fn make_progress(shape: &CellShape, place: &Place, progress: f64, fill: &str) -> Option<Element> {
+	if progress <= 0.0 {
+		return None;
+	}
+	let progress = progress.min(1.0);
+	let size = f64::from(place.size);
+	let bar = Rectangle::new()
+		.set("x", place.x)
+		.set("y", place.y)
+		.set("width", size * progress)
+		.set("height", size)
+		.set("fill", fill);
+	Some(match *shape.shape {
+		SvgShape::Square if !shape.use_symbol => bar.into(),
+		SvgShape::Circle if !shape.use_symbol => {
+			let (cx, cy, r) = (f64::from(place.cx), f64::from(place.cy), size / 2.0);
+			let angle = progress * TAU;
+			let end_x = r.mul_add(angle.sin(), cx);
+			let end_y = r.mul_add(-angle.cos(), cy);
+			let large_arc = u8::from(progress > 0.5);
+			// An arc can't start and end at the same point, so a whole pie is just a circle.
+			if progress >= 1.0 {
+				Circle::new()
+					.set("cx", cx)
+					.set("cy", cy)
+					.set("r", r)
+					.set("fill", fill)
+					.into()
+			} else {
+				Path::new()
+					.set(
+						"d",
+						format!(
+							"M {cx} {cy} L {cx} {} A {r} {r} 0 {large_arc} 1 {end_x} {end_y} Z",
+							cy - r
+						),
+					)
+					.set("fill", fill)
+					.into()
+			}
+		},
+		_ => {
+			// Only the present cell shows its progress, so one clip path is enough.
+			let clip_id = "present-progress";
+			Group::new()
+				.add(
+					ClipPath::new()
+						.set("id", clip_id)
+						.add(make_shape(shape, place, "black", "none", 0)),
+				)
+				.add(bar.set("clip-path", format!("url(#{clip_id})")))
+				.into()
+		},
+	})
+}
+
+/// The shapes a shape's SVG file can be drawn with, and the attributes that give their outlines.
+const SYMBOL_SHAPES: [(&str, &[&str]); 7] = [
+	(tag::Path, &["d", "fill-rule"]),
+	(tag::Circle, &["cx", "cy", "r"]),
+	(tag::Ellipse, &["cx", "cy", "rx", "ry"]),
+	(tag::Rectangle, &["x", "y", "width", "height", "rx", "ry"]),
+	(tag::Polygon, &["points", "fill-rule"]),
+	(tag::Polyline, &["points"]),
+	(tag::Line, &["x1", "y1", "x2", "y2"]),
+];
+
+/// Elements of a shape's SVG file that don't draw anything, and are left out along with what's in
+/// them. So are elements from other namespaces, like the ones editors keep their settings in.
+const SYMBOL_IGNORED: [&str; 5] = [
+	tag::Definitions,
+	tag::Title,
+	tag::Description,
+	tag::Style,
+	"metadata",
+];
+
+/// Turn the shapes of an SVG file into a `<symbol>` that can be drawn in place of a cell.
+///
+/// Only the `viewBox` (or failing that the size) of the file, the outlines of its shapes and the
+/// groups they're in, along with their transforms, are kept, so each cell can give the shape its
+/// own colors. Anything else that would be drawn, like text or images, is an error.
+fn parse_symbol(content: &str) -> Result<Symbol> {
+	let copy = |name: &str, attributes: &svg::node::Attributes, keep: &[&str]| {
+		let mut element = Element::new(name);
+		for &key in keep.iter().chain(&["transform"]) {
+			if let Some(value) = attributes.get(key) {
+				element.assign(key, value.clone());
+			}
+		}
+		element
+	};
+	let mut view_box = None;
+	let mut started = false;
+	// Groups that haven't been closed yet, innermost last
+	let mut groups: Vec<Element> = Vec::new();
+	let mut elements = Vec::new();
+	let mut shape_count = 0;
+	// How deep inside something that isn't drawn, or inside a shape, the parser is
+	let mut skipping = 0_u32;
+	for event in svg::read(content)? {
+		let (name, kind, attributes) = match event {
+			Event::Tag(name, kind, attributes) => (name, kind, attributes),
+			Event::Error(error) => bail!("{error}"),
+			_ => continue,
+		};
+		if skipping > 0 {
+			match kind {
+				Type::Start => skipping += 1,
+				Type::End => skipping -= 1,
+				Type::Empty => {},
+			}
+			continue;
+		}
+		let element = match (name, kind) {
+			(tag::SVG, Type::Start) if !started => {
+				started = true;
+				view_box = attributes
+					.get("viewBox")
+					.map(ToString::to_string)
+					.or_else(|| {
+						let width = attributes.get("width")?;
+						let height = attributes.get("height")?;
+						Some(format!("0 0 {} {}", &**width, &**height))
+					});
+				continue;
+			},
+			(tag::Group, Type::Start) => {
+				groups.push(copy(name, &attributes, &[]));
+				continue;
+			},
+			(tag::Group, Type::End) => match groups.pop() {
+				Some(group) => group,
+				None => bail!("The shape's SVG file closes a group it never opened"),
+			},
+			(_, Type::End) | (tag::Group, Type::Empty) => continue,
+			_ if SYMBOL_IGNORED.contains(&name) || name.contains(':') => {
+				if kind == Type::Start {
+					skipping = 1;
+				}
+				continue;
+			},
+			_ => {
+				let Some((_, keep)) = SYMBOL_SHAPES.iter().find(|(shape, _)| *shape == name) else {
+					let shapes = SYMBOL_SHAPES
+						.map(|(shape, _)| format!("<{shape}>"))
+						.join(", ");
+					bail!("The shape's SVG file has a <{name}>, but only {shapes} and <g> can be used");
+				};
+				if kind == Type::Start {
+					skipping = 1;
+				}
+				shape_count += 1;
+				// Keep the outline as thick as the other shapes' no matter how big the file's
+				// drawing is.
+				let mut shape = copy(name, &attributes, keep);
+				shape.assign("vector-effect", "non-scaling-stroke");
+				shape
+			},
+		};
+		match groups.last_mut() {
+			Some(group) => group.append(element),
+			None => elements.push(element),
 		}
 	}
+	let Some(view_box) = view_box else {
+		bail!("The shape's SVG file needs a viewBox, or a width and height");
+	};
+	if shape_count == 0 {
+		bail!("The shape's SVG file doesn't have any shapes in it");
+	}
+	Ok(elements.into_iter().fold(
+		Symbol::new().set("id", SYMBOL_ID).set("viewBox", view_box),
+		Symbol::add,
+	))
 }
 
-#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
-pub enum SvgShape {
-	Square,
-	Circle,
+/// Number the labels along an axis should show, if that position gets a label at all.
+fn axis_label(interval: Option<u32>, value: u32, is_first: bool) -> Option<u32> {
+	let interval = interval?;
+	(is_first || (interval > 0 && value.is_multiple_of(interval))).then_some(value)
 }
 
-pub fn render_svg(
+/// Draw a grid onto a canvas, which `begin` makes out of the document holding the background.
+///
+/// Streaming the image straight to a file this way means the whole grid never has to be in memory
+/// at once.
+pub fn render<C: Canvas>(
 	birth_info: &LifeInfo,
 	drawing_info: &DrawingInfoValidated,
 	drawing_ratios: &GridRatios,
-	week_shape: &SvgShape,
-) -> Result<Document> {
-	let color_primary = drawing_info.color_primary.to_hex_string();
-	let color_secondary = drawing_info.color_secondary.to_hex_string();
+	style: &GridStyle,
+	layout: &GridLayout,
+	labels: &GridLabels,
+	begin: impl FnOnce(Document) -> Result<C>,
+) -> Result<C> {
+	let color_present = drawing_info.color_present.to_hex_string();
+	let color_stroke = drawing_info.color_stroke.to_hex_string();
+	let color_text = drawing_info.color_text.to_hex_string();
 	let scale_factor = drawing_info.scale_factor;
 
 	let bday = birth_info.birthday;
@@ -77,84 +760,637 @@ pub fn render_svg(
 	let today = Date::today_utc();
 	let end = death_day(bday, lifespan_years.try_into()?);
 
-	let stroke_width = drawing_ratios.stroke * scale_factor * 2;
+	let unit = &layout.unit;
+	let (lines, line_length, line_span) = if let Alignment::Compressed = layout.align {
+		let line_length = layout.wrap.unwrap_or_else(|| unit.line_length());
+		let lines = cells(unit, bday, end)
+			.chunks(line_length.try_into()?)
+			.map(|cells| Line {
+				first_pos: 0,
+				cells: cells.to_vec(),
+			})
+			.collect::<Vec<_>>();
+		(lines, line_length, line_length)
+	} else {
+		if layout.wrap.is_some() {
+			bail!("Lines can't be wrapped when they're aligned to real years.");
+		}
+		let lines = aligned_lines(unit, &layout.align, &layout.week_start, bday, end);
+		let line_length = lines
+			.iter()
+			.map(|line| line.first_pos + u32::try_from(line.cells.len()).unwrap_or(u32::MAX))
+			.max()
+			.unwrap_or(1);
+		(lines, line_length, unit.cells_per_year())
+	};
+	let line_count = u32::try_from(lines.len())?;
+
+	// Every size is checked as it's worked out, so a big scale factor gives an error instead of
+	// overflowing.
+	let scaled = |size: u32| pixels(f64::from(size) * f64::from(scale_factor));
+	let stroke_width = pixels(f64::from(scaled(drawing_ratios.stroke)?) * 2.0)?;
 
-	let padding = drawing_ratios.padding * scale_factor;
-	let inner_shape_size = (drawing_ratios.length * 2) * scale_factor + stroke_width;
-	let outer_shape_size = inner_shape_size + (padding * 2) + stroke_width;
+	let padding = scaled(drawing_ratios.padding)?;
+	let inner_shape_size =
+		pixels(f64::from(scaled(drawing_ratios.length)?).mul_add(2.0, f64::from(stroke_width)))?;
+	let outer_shape_size = pixels(
+		f64::from(padding).mul_add(2.0, f64::from(inner_shape_size) + f64::from(stroke_width)),
+	)?;
 
-	let border = match drawing_ratios.border_unit {
-		BorderUnit::Pixel => drawing_ratios.border * scale_factor,
-		BorderUnit::Shape => drawing_ratios.border * outer_shape_size,
+	let week_shape = &CellShape {
+		shape: &style.week_shape,
+		corner_radius: scaled(style.corner_radius)?,
+		orientation: &layout.orientation,
+		use_symbol: style.week_shape_file.is_some(),
+	};
+	// Hexagons nestle into the gaps of the line before, so lines sit closer together. Their
+	// corners stick out past their slot, so the border grows to make room for them.
+	let (line_pitch, stagger, overhang) = match (&style.week_shape, week_shape.use_symbol) {
+		(SvgShape::Hexagon, false) => {
+			let inner_shape_size = f64::from(inner_shape_size);
+			#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+			let (line_pitch, overhang) = (
+				(f64::from(outer_shape_size) * 3_f64.sqrt() / 2.0).round() as u32,
+				(inner_shape_size / 3_f64.sqrt() - inner_shape_size / 2.0).ceil() as u32,
+			);
+			(
+				line_pitch,
+				outer_shape_size / 2,
+				overhang.saturating_sub(padding),
+			)
+		},
+		_ => (outer_shape_size, 0, 0),
+	};
+
+	let border = pixels(
+		f64::from(overhang)
+			+ match drawing_ratios.border_unit {
+				BorderUnit::Pixel => f64::from(scaled(drawing_ratios.border)?),
+				BorderUnit::Shape => f64::from(drawing_ratios.border) * f64::from(outer_shape_size),
+			},
+	)?;
+
+	let font_size = scaled(labels.label_font_size)?;
+	// Leave room for the widest age label next to the lines, and a row of numbers along them.
+	let max_age_digits = num_to_charcount(lifespan_years.into())?;
+	let digits_size = |digits: u32| f64::from(font_size) * f64::from(digits + 1) * 3.0 / 5.0;
+	let line_label_size = match (&labels.year_label_interval, &layout.orientation) {
+		(None, _) => 0,
+		(Some(_), Orientation::Rows) => pixels(digits_size(max_age_digits))?,
+		(Some(_), Orientation::Columns) => font_size * 3 / 2,
+	};
+	let cell_label_size = match (&labels.cell_label_interval, &layout.orientation) {
+		(None, _) => 0,
+		(Some(_), Orientation::Rows) => font_size * 3 / 2,
+		(Some(_), Orientation::Columns) => pixels(digits_size(num_to_charcount(line_length)?))?,
+	};
+
+	let spacing = Spacing {
+		orientation: &layout.orientation,
+		slot_size: outer_shape_size,
+		line_pitch,
+		stagger,
+		gutter: scaled(drawing_ratios.gutter)?,
+		line_length,
+		line_span,
+		cells_per_year: unit.cells_per_year(),
+		year_gutter_interval: layout.year_gutter_interval,
+		cell_gutter_interval: layout.cell_gutter_interval,
+	};
+	let lines_size = spacing.lines_size(line_count)?;
+	let line_size = spacing.line_size()?;
+
+	// Lines of the grid go down the image as columns, or across it as rows.
+	let (columns, rows, label_width, label_height) = match layout.orientation {
+		Orientation::Columns => (lines_size, line_size, cell_label_size, line_label_size),
+		Orientation::Rows => (line_size, lines_size, line_label_size, cell_label_size),
 	};
 
 	// In total, the outer dimensions of a shape is a function of its stroke-width x 2,
 	// hence the variable `space_around_shape`.
-	let grid_width = outer_shape_size * u32::from(lifespan_years);
-	let grid_height = outer_shape_size * WEEKS_IN_A_YEAR;
+	let grid_width = columns;
+	let grid_height = rows;
+
+	// Phases and events are listed under the grid, one to a line.
+	let legend_line_height = font_size * 3 / 2;
+	let legend_height = match u32::try_from(drawing_info.phases.len() + drawing_info.events.len())?
+	{
+		0 => 0,
+		entry_count => pixels(
+			f64::from(entry_count) * f64::from(legend_line_height) + f64::from(font_size / 2),
+		)?,
+	};
 
-	let viewbox_width = grid_width + (border * 2) + (padding * 2);
-	let viewbox_height = grid_height + (border * 2) + (padding * 2);
+	let around = 2.0 * (f64::from(border) + f64::from(padding));
+	let viewbox_width = pixels(f64::from(grid_width) + around + f64::from(label_width))?;
+	let viewbox_height = pixels(
+		f64::from(grid_height) + around + f64::from(label_height) + f64::from(legend_height),
+	)?;
 
 	let mut document = init_document(
 		viewbox_width.into(),
 		viewbox_height.into(),
-		&color_secondary,
-	);
+		drawing_info,
+		birth_info,
+	)?;
+	if let Some(path) = &style.week_shape_file {
+		let content = fs::read_to_string(path)
+			.with_context(|| format!("Could not read {}", path.display()))?;
+		document.append(
+			parse_symbol(&content)
+				.with_context(|| format!("Could not use {} as a shape", path.display()))?,
+		);
+	}
+	let mut canvas = begin(document)?;
 
-	let mut count = 0;
-	let mut curr_date = bday;
-	while curr_date < end {
-		// There's an off-by-one error if we do not add 7 days to the current date. It will show
-		// that one week has passed since the person's birthday on their birthday, which is not
-		// correct.
-		let fill = if curr_date.add_days(7) <= today {
-			color_primary.as_str()
+	let grid_x = border + padding + label_width;
+	let grid_y = border + padding + label_height;
+	let slot = |line: u32, pos: u32| spacing.slot(line, pos);
+
+	let make_label = |text: u32, x: u32, y: u32, anchor: &str| {
+		Text::new(text.to_string())
+			.set("x", x)
+			.set("y", y)
+			.set("fill", color_text.as_str())
+			.set("font-family", labels.label_font_family.as_str())
+			.set("font-size", format!("{font_size}px"))
+			.set("text-anchor", anchor)
+			.set("dominant-baseline", "central")
+	};
+	let (line_label_anchor, cell_label_anchor) = match layout.orientation {
+		Orientation::Columns => ("middle", "end"),
+		Orientation::Rows => ("end", "middle"),
+	};
+	// Labels sit in the margin before the first shape of each line or position.
+	let label_position = |slot_x: u32, slot_y: u32, is_line_label: bool| -> (u32, u32) {
+		let center = outer_shape_size / 2;
+		let gap = font_size / 2;
+		let along_top = matches!(
+			(is_line_label, &layout.orientation),
+			(true, Orientation::Columns) | (false, Orientation::Rows)
+		);
+		if along_top {
+			(grid_x + slot_x + center, grid_y - gap - font_size / 2)
 		} else {
-			color_secondary.as_str()
+			(grid_x - gap, grid_y + slot_y + center)
+		}
+	};
+
+	for line in 0..line_count {
+		let age = spacing.age_at_line(line);
+		let starts_new_age = line == 0 || spacing.age_at_line(line - 1) != age;
+		if let Some(age) =
+			axis_label(labels.year_label_interval, age, line == 0).filter(|_| starts_new_age)
+		{
+			let (slot_x, slot_y) = slot(line, 0);
+			let (x, y) = label_position(slot_x, slot_y, true);
+			canvas.draw(make_label(age, x, y, line_label_anchor))?;
+		}
+	}
+	for pos in 0..line_length {
+		if let Some(number) = axis_label(labels.cell_label_interval, pos + 1, pos == 0) {
+			let (slot_x, slot_y) = slot(0, pos);
+			let (x, y) = label_position(slot_x, slot_y, false);
+			canvas.draw(make_label(number, x, y, cell_label_anchor))?;
+		}
+	}
+
+	let cells = (0_u32..).zip(&lines).flat_map(|(line_num, line)| {
+		(line.first_pos..)
+			.zip(&line.cells)
+			.map(move |(pos, cell)| (line_num, pos, cell))
+	});
+	let mut classes = StyleClasses::default();
+	let mut patterns = Patterns::new(
+		f64::from(inner_shape_size) / 5.0,
+		&drawing_info.color_stroke,
+		&drawing_info.color_background,
+	);
+	for (number, (line_num, pos, cell)) in (1_i64..).zip(cells) {
+		let (slot_x, slot_y) = slot(line_num, pos);
+		// For example "Week 1,234: 2035-03-04 to 2035-03-10, age 22"
+		let mut tooltip = labels.cell_tooltips.then(|| {
+			Title::new(format!(
+				"{} {}: {} to {}, age {}",
+				unit.name(),
+				group_digits(number),
+				cell.start,
+				cell.end.prev(),
+				years_lived(cell.start, bday)
+			))
+		});
+
+		let place = Place {
+			x: grid_x + slot_x + padding + (stroke_width / 2),
+			y: grid_y + slot_y + padding + (stroke_width / 2),
+			// Both shapes sit in the middle of their slot.
+			cx: grid_x + slot_x + (outer_shape_size / 2),
+			cy: grid_y + slot_y + (outer_shape_size / 2),
+			size: inner_shape_size,
 		};
 
-		let x_offset = ((viewbox_width - grid_width) / 2) + padding + (stroke_width / 2);
-		let x = ((count / WEEKS_IN_A_YEAR) * outer_shape_size) + x_offset;
-		let y_offset = ((viewbox_height - grid_height) / 2) + padding + (stroke_width / 2);
-		let y = ((count % WEEKS_IN_A_YEAR) * outer_shape_size) + y_offset;
-
-		let cx_offset = ((viewbox_width - grid_width) / 2) + (padding / 2) + (outer_shape_size / 2);
-		let cx = ((count / WEEKS_IN_A_YEAR) * outer_shape_size) + cx_offset;
-		let cy_offset =
-			((viewbox_height - grid_height) / 2) + (padding / 2) + (outer_shape_size / 2);
-		let cy = ((count % WEEKS_IN_A_YEAR) * outer_shape_size) + cy_offset;
-		let shape: Element = match *week_shape {
-			SvgShape::Square => Rectangle::new()
-				.set("x", x)
-				.set("y", y)
-				.set("width", inner_shape_size)
-				.set("height", inner_shape_size)
-				.set("fill", fill)
-				.set("stroke", color_primary.as_str())
-				.set("stroke-width", stroke_width)
-				.into(),
-			SvgShape::Circle => Circle::new()
-				.set("cx", cx)
-				.set("cy", cy)
-				.set("r", inner_shape_size / 2)
-				.set("fill", fill)
-				.set("stroke", color_primary.as_str())
-				.set("stroke-width", stroke_width)
-				.into(),
+		// Cells with an event in them stand out with a thicker outline in the event's color.
+		let event = drawing_info
+			.events
+			.iter()
+			.find(|event| event.overlaps(cell.start, cell.end));
+		let (stroke, cell_stroke_width) = event
+			.map_or((color_stroke.clone(), stroke_width), |event| {
+				(event.color.to_hex_string(), stroke_width * 2)
+			});
+		// Cells in a phase take on its colors. Gradients are colored by how far the start of a
+		// cell is from birth when it's lived, or from today when it's still to come.
+		let (past_color, future_color) = phases::find(&drawing_info.phases, cell.start)
+			.map_or_else(
+				|| match drawing_info.gradient {
+					Some(ref gradient) => {
+						let share = |from: Date, to: Date| {
+							f64::from(Date::days_since(from, cell.start))
+								/ f64::from(Date::days_since(from, to).max(1))
+						};
+						(
+							gradient.past_at(share(bday, today)),
+							gradient.future_at(share(today, end)),
+						)
+					},
+					None => (
+						drawing_info.color_primary.clone(),
+						drawing_info.color_secondary.clone(),
+					),
+				},
+				|phase| (phase.color_past.clone(), phase.color_future.clone()),
+			);
+
+		// A cell is only filled in once all of it has passed. Otherwise it would show that one
+		// week has passed since the person's birthday on their birthday, which is not correct.
+		let mut element = if cell.start <= today && today < cell.end {
+			let outline =
+				|fill: &str| make_shape(week_shape, &place, fill, &color_present, stroke_width * 2);
+			let progress = f64::from(Date::days_since(cell.start, today))
+				/ f64::from(Date::days_since(cell.start, cell.end));
+			let present_fill = patterns.fill(
+				drawing_info.present_pattern,
+				&future_color,
+				&drawing_info.color_present,
+			);
+			let past_fill = patterns.contrasting_fill(drawing_info.past_pattern, &past_color);
+			match make_progress(week_shape, &place, progress, &past_fill) {
+				Some(progress) if style.present_progress => {
+					// A tooltip has to be the first thing in a group for it to be found.
+					let group = tooltip
+						.take()
+						.map_or_else(Group::new, |title| Group::new().add(title));
+					group
+						.add(outline(&present_fill))
+						.add(progress)
+						.add(outline("none"))
+						.into()
+				},
+				_ => outline(&present_fill),
+			}
+		} else {
+			let (pattern, ground) = if cell.end <= today {
+				(drawing_info.past_pattern, &past_color)
+			} else {
+				(drawing_info.future_pattern, &future_color)
+			};
+			// Events have their own pattern, drawn in their color.
+			let fill = match event {
+				Some(event) if drawing_info.event_pattern != Pattern::Solid => {
+					patterns.fill(drawing_info.event_pattern, ground, &event.color)
+				},
+				_ => patterns.contrasting_fill(pattern, ground),
+			};
+			if drawing_info.compact {
+				make_compact_shape(&place, &classes.class(&fill, &stroke, cell_stroke_width)).into()
+			} else {
+				make_shape(week_shape, &place, &fill, &stroke, cell_stroke_width)
+			}
 		};
+		if let Some(title) = tooltip {
+			element.append(title);
+		}
+		// Web pages read these to show what's under the mouse.
+		if drawing_info.interactive {
+			let events = drawing_info
+				.events
+				.iter()
+				.filter(|event| event.overlaps(cell.start, cell.end))
+				.map(|event| event.name.as_str())
+				.collect::<Vec<_>>();
+			element.assign("data-start", cell.start.to_string());
+			element.assign("data-end", cell.end.prev().to_string());
+			element.assign("data-age", years_lived(cell.start, bday));
+			if !events.is_empty() {
+				element.assign("data-events", events.join(", "));
+			}
+		}
+		canvas.draw(element)?;
+	}
+	// A style sheet and definitions hold for the whole image wherever they are, so compact cells
+	// can be written out before them, once every class they use is known.
+	if drawing_info.compact {
+		// Cells are drawn relative to the top left corner of their shape.
+		let center = outer_shape_size / 2 - padding - (stroke_width / 2);
+		let mut cell_shape = make_outline(
+			week_shape,
+			&Place {
+				x: 0,
+				y: 0,
+				cx: center,
+				cy: center,
+				size: inner_shape_size,
+			},
+		);
+		cell_shape.assign("id", CELL_ID);
+		canvas.draw(classes.to_style())?;
+		canvas.draw(Definitions::new().add(cell_shape))?;
+	}
+
+	let legend_y = grid_y + grid_height + padding + font_size / 2;
+	let swatch = |line: u32, index: u32| {
+		let x = grid_x + index * font_size * 3 / 2;
+		let y = legend_y + line * legend_line_height;
+		Place {
+			x: x + stroke_width,
+			y: y + stroke_width,
+			cx: x + font_size / 2,
+			cy: y + font_size / 2,
+			size: font_size.saturating_sub(stroke_width * 2),
+		}
+	};
+	let legend_label = |line: u32, swatches: u32, text: &str| {
+		Text::new(text)
+			.set("x", grid_x + swatches * font_size * 3 / 2)
+			.set("y", legend_y + line * legend_line_height + font_size / 2)
+			.set("fill", color_text.as_str())
+			.set("font-family", labels.label_font_family.as_str())
+			.set("font-size", format!("{font_size}px"))
+			.set("dominant-baseline", "central")
+	};
+	// Each phase shows both its past and future color.
+	for (line, phase) in (0_u32..).zip(&drawing_info.phases) {
+		for (index, color) in (0..).zip([&phase.color_past, &phase.color_future]) {
+			canvas.draw(make_shape(
+				week_shape,
+				&swatch(line, index),
+				&color.to_hex_string(),
+				&color_stroke,
+				stroke_width,
+			))?;
+		}
+		canvas.draw(legend_label(line, 2, &phase.name))?;
+	}
+	for (line, event) in (u32::try_from(drawing_info.phases.len())?..).zip(&drawing_info.events) {
+		let fill = patterns.fill(
+			drawing_info.event_pattern,
+			&drawing_info.color_secondary,
+			&event.color,
+		);
+		canvas.draw(make_shape(
+			week_shape,
+			&swatch(line, 0),
+			&fill,
+			&event.color.to_hex_string(),
+			stroke_width * 2,
+		))?;
+		canvas.draw(legend_label(line, 1, &event.name))?;
+	}
+	// Patterns can be used before they're defined, so they're all defined once they're known.
+	if !patterns.is_empty() {
+		canvas.draw(patterns.to_definitions())?;
+	}
+	Ok(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+	use clap::Parser;
+	use csscolorparser::Color;
+
+	use super::*;
+	use crate::color::{self, Gradient};
+
+	fn date(year: i16, month: u8, day: u8) -> Date {
+		Date::new(year, month, day).unwrap()
+	}
 
-		document.append(shape);
-		// All this below is just to make sure there are always 52 weeks. To do this, we change the
-		// number of days in a week and skip the 29th of February whenever it comes.
-		let week_length = if count % 52 == 0 { 8 } else { 7 };
-		for _ in 0_u8..week_length {
-			curr_date = curr_date.next();
-			if curr_date.month() == 2 && curr_date.day() == 29 {
-				curr_date = curr_date.next();
+	#[test]
+	fn every_line_starts_on_a_birthday() {
+		let bday = date(1996, 3, 1);
+		let end = death_day(bday, 100);
+		for unit in [
+			GridUnit::Day,
+			GridUnit::Week,
+			GridUnit::Month,
+			GridUnit::Quarter,
+		] {
+			let cells = cells(&unit, bday, end);
+			let line_length = unit.line_length() as usize;
+			assert_eq!(cells.len(), line_length * 100);
+			for (year, line) in (0_i16..).zip(cells.chunks(line_length)) {
+				assert_eq!(line[0].start, bday.add_years(year).unwrap());
 			}
 		}
-		count += 1;
+		assert_eq!(cells(&GridUnit::Year, bday, end).len(), 100);
+	}
+
+	#[test]
+	fn cells_cover_the_whole_life_without_gaps() {
+		let bday = date(2000, 1, 31);
+		let end = death_day(bday, 80);
+		let cells = cells(&GridUnit::Month, bday, end);
+		assert_eq!(cells[0].start, bday);
+		assert_eq!(cells[0].end, date(2000, 2, 29));
+		assert_eq!(cells[1].start, date(2000, 2, 29));
+		assert_eq!(cells[1].end, date(2000, 3, 31));
+		assert_eq!(cells.last().unwrap().end, end);
+		assert!(cells.windows(2).all(|pair| pair[0].end == pair[1].start));
+	}
+
+	#[test]
+	fn gutters_separate_decades_and_quarters() {
+		let spacing = Spacing {
+			orientation: &Orientation::Rows,
+			slot_size: 10,
+			line_pitch: 10,
+			stagger: 0,
+			gutter: 4,
+			line_length: 52,
+			line_span: 52,
+			cells_per_year: 52,
+			year_gutter_interval: 10,
+			cell_gutter_interval: Some(13),
+		};
+		assert_eq!(spacing.slot(9, 12), (120, 90));
+		assert_eq!(spacing.slot(10, 13), (134, 104));
+		assert_eq!(spacing.lines_size(100).unwrap(), 1000 + 9 * 4);
+		assert_eq!(spacing.line_size().unwrap(), 520 + 3 * 4);
+	}
+
+	#[test]
+	fn honeycomb_lines_are_staggered_and_closer_together() {
+		let spacing = Spacing {
+			orientation: &Orientation::Columns,
+			slot_size: 36,
+			line_pitch: 31,
+			stagger: 18,
+			gutter: 0,
+			line_length: 52,
+			line_span: 52,
+			cells_per_year: 52,
+			year_gutter_interval: 0,
+			cell_gutter_interval: None,
+		};
+		assert_eq!(spacing.slot(0, 0), (0, 0));
+		assert_eq!(spacing.slot(1, 0), (31, 18));
+		assert_eq!(spacing.slot(2, 1), (62, 36));
+		assert_eq!(spacing.line_size().unwrap(), 52 * 36 + 18);
+	}
+
+	#[test]
+	fn gradients_run_from_birth_to_today_and_from_today_to_death() {
+		let parse = |hex| csscolorparser::parse(hex).unwrap();
+		let mut drawing_info = super::super::tests::drawing_info(false, false);
+		drawing_info.color_primary = parse("navy");
+		drawing_info.color_secondary = parse("white");
+		let gradient = Gradient {
+			birth: parse("orange"),
+			past: drawing_info.color_primary.clone(),
+			future: drawing_info.color_secondary.clone(),
+			death: parse("#dddddd"),
+		};
+		drawing_info.gradient = Some(gradient.clone());
+		let life_info = LifeInfo {
+			birthday: date(1990, 5, 5),
+			lifespan_years: 80,
+		};
+		let document = render(
+			&life_info,
+			&drawing_info,
+			&GridRatios::parse_from(["grid"]),
+			&GridStyle::parse_from(["grid"]),
+			&GridLayout::parse_from(["grid"]),
+			&GridLabels::parse_from(["grid"]),
+			Ok,
+		)
+		.unwrap()
+		.to_string();
+		// Every cell has an outline, which the background doesn't, and the present's is red.
+		let cells = document
+			.lines()
+			.filter(|line| line.starts_with("<rect") && line.contains("stroke="))
+			.map(|line| {
+				let fill = line.split("fill=\"").nth(1).unwrap();
+				(parse(&fill[..7]), line.contains(r##"stroke="#ff0000""##))
+			})
+			.collect::<Vec<_>>();
+		let present = cells.iter().position(|&(_, present)| present).unwrap();
+		let close = |cell: usize, expected: &Color| {
+			let distance = color::distance(&cells[cell].0, expected);
+			assert!(distance < 0.01, "cell {cell} is {distance} away");
+		};
+		close(0, &gradient.birth);
+		close(present - 1, &gradient.past);
+		close(present + 1, &gradient.future);
+		let middle = color::distance(&cells[present / 2].0, &gradient.birth);
+		assert!(middle > color::MIN_DISTANCE, "{middle}");
+	}
+
+	#[test]
+	fn symbols_keep_the_view_box_and_shapes() {
+		let symbol = parse_symbol(
+			r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24">
+				<title>Icon</title>
+				<sodipodi:namedview pagecolor="white"/>
+				<defs><path d="M 1 1"/></defs>
+				<path d="M 0 0 L 24 24" fill="red"/>
+				<g transform="rotate(45 12 12)"><path d="M 24 0 L 0 24"/><circle cx="12" cy="12" r="4"/></g>
+				<rect x="2" y="2" width="4" height="4" style="fill: blue"/>
+				<polygon points="0,0 4,0 2,4"/>
+			</svg>"#,
+		)
+		.unwrap()
+		.to_string();
+		assert!(symbol.contains(r#"viewBox="0 0 24 24""#));
+		assert!(symbol.contains(r#"d="M 0 0 L 24 24""#));
+		assert!(symbol.contains(r#"<g transform="rotate(45 12 12)">"#));
+		assert!(symbol.contains(r#"d="M 24 0 L 0 24""#));
+		assert!(symbol.contains(r#"<circle cx="12" cy="12" r="4""#));
+		assert!(symbol.contains(
+			r#"<rect height="4" vector-effect="non-scaling-stroke" width="4" x="2" y="2"/>"#
+		));
+		assert!(symbol.contains(r#"<polygon points="0,0 4,0 2,4""#));
+		assert!(!symbol.contains("M 1 1"));
+		assert!(!symbol.contains("Icon"));
+		assert!(!symbol.contains("red"));
+		assert!(!symbol.contains("blue"));
+		assert!(parse_symbol(r#"<svg viewBox="0 0 1 1"></svg>"#).is_err());
+		let error = parse_symbol(r#"<svg viewBox="0 0 1 1"><path d="M 0 0"/><text>A</text></svg>"#)
+			.unwrap_err()
+			.to_string();
+		assert!(error.contains("<text>"), "{error}");
+	}
+
+	#[test]
+	fn weeks_start_on_the_right_weekday() {
+		assert_eq!(
+			Weekday::Monday.on_or_before(date(2024, 1, 1)),
+			date(2024, 1, 1)
+		);
+		assert_eq!(
+			Weekday::Monday.on_or_before(date(2024, 1, 7)),
+			date(2024, 1, 1)
+		);
+		assert_eq!(
+			Weekday::Sunday.on_or_before(date(2024, 1, 6)),
+			date(2023, 12, 31)
+		);
+		assert_eq!(Weekday::Thursday.on_or_before(date(1, 1, 4)), date(1, 1, 4));
+	}
+
+	#[test]
+	fn calendar_weeks_match_iso_weeks() {
+		let bday = date(2015, 1, 1);
+		let end = death_day(bday, 10);
+		let lines = aligned_lines(
+			&GridUnit::Week,
+			&Alignment::Calendar,
+			&Weekday::Monday,
+			bday,
+			end,
+		);
+		// 2015 and 2020 have 53 ISO weeks, the rest have 52. The last two days of 2024 fall in
+		// the first week of 2025.
+		let week_counts: Vec<_> = lines
+			.iter()
+			.map(|line| line.first_pos as usize + line.cells.len())
+			.collect();
+		assert_eq!(week_counts, [53, 52, 52, 52, 52, 53, 52, 52, 52, 52, 1]);
+		// ISO week 1 of 2015 started on Monday the 29th of December 2014, before this birthday.
+		assert_eq!(lines[0].first_pos, 0);
+		assert_eq!(lines[0].cells[0].start, bday);
+		assert_eq!(lines[0].cells[0].end, date(2015, 1, 5));
+		assert_eq!(lines[1].cells[0].start, date(2016, 1, 4));
+		let cells: Vec<_> = lines.iter().flat_map(|line| &line.cells).collect();
+		assert!(cells.windows(2).all(|pair| pair[0].end == pair[1].start));
+		assert_eq!(cells.last().unwrap().end, end);
+	}
+
+	#[test]
+	fn birthday_years_keep_leap_days() {
+		let bday = date(1996, 2, 29);
+		let end = death_day(bday, 8);
+		let lines = aligned_lines(
+			&GridUnit::Day,
+			&Alignment::Birthday,
+			&Weekday::Monday,
+			bday,
+			end,
+		);
+		let day_counts: Vec<_> = lines.iter().map(|line| line.cells.len()).collect();
+		assert_eq!(day_counts, [365, 365, 365, 366, 365, 365, 365, 366]);
+		assert_eq!(lines[1].cells[0].start, date(1997, 2, 28));
+		assert_eq!(lines[4].cells[0].start, date(2000, 2, 29));
 	}
-	Ok(document)
 }
@@ -0,0 +1,341 @@
+use anyhow::Result;
+use death_calendar::{
+	lifespan_months, lifespan_weeks, months_left, months_lived, weeks_left, weeks_lived,
+};
+use gregorian::Date;
+use serde::{Deserialize, Serialize};
+use svg::{
+	node::element::{Circle, Definitions, Line, Polygon, Use},
+	Document, Node,
+};
+
+use super::{init_document, StyleClasses};
+use crate::{DrawingInfoValidated, HourglassStyle, LifeInfo};
+
+/// How many points are sampled along each side of a bulb when drawing its outline.
+const OUTLINE_SAMPLES: u32 = 64;
+/// Width of the narrowest part of the glass, as a fraction of the widest part.
+const NECK_RATIO: f64 = 0.04;
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GrainUnit {
+	Week,
+	Month,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BulbShape {
+	/// Two straight-sided cones meeting at the neck
+	Cone,
+	/// Rounded bulbs, widest a little past their middle
+	Round,
+	/// A short funnel opening into straight walls
+	Flask,
+}
+
+impl BulbShape {
+	/// Half the width of a bulb, from 0 to 1, at a distance `t` from the neck.
+	///
+	/// `t` goes from 0 at the neck to 1 at the far end of the bulb.
+	fn half_width(&self, t: f64) -> f64 {
+		let t = t.clamp(0.0, 1.0);
+		let widening = match *self {
+			Self::Cone => t,
+			Self::Round => (1.0 - ((t - 0.6) / 0.6).powi(2)).max(0.0).sqrt(),
+			Self::Flask => (t / 0.4).min(1.0),
+		};
+		(1.0 - NECK_RATIO).mul_add(widening, NECK_RATIO)
+	}
+}
+
+/// A bulb of the hourglass, in image coordinates.
+struct Bulb<'a> {
+	shape: &'a BulbShape,
+	/// Horizontal center of the glass
+	center_x: f64,
+	/// Half the width of the glass at its widest
+	max_half_width: f64,
+	/// Vertical position of the neck
+	neck_y: f64,
+	/// Vertical position of the far end of the bulb
+	end_y: f64,
+}
+
+impl Bulb<'_> {
+	fn half_width_at(&self, y: f64) -> f64 {
+		let t = (y - self.neck_y) / (self.end_y - self.neck_y);
+		self.shape.half_width(t) * self.max_half_width
+	}
+
+	/// The narrowest half width between two heights, sampled at both ends and the middle.
+	fn min_half_width_between(&self, y1: f64, y2: f64) -> f64 {
+		[y1, (y1 + y2) / 2.0, y2]
+			.into_iter()
+			.map(|y| self.half_width_at(y))
+			.fold(f64::MAX, f64::min)
+	}
+
+	/// Stack grains row by row from the bottom of the bulb, using hexagonal packing.
+	///
+	/// Returns the centers of at most `count` grains of the given diameter.
+	fn pack(&self, count: u32, diameter: f64) -> Vec<(f64, f64)> {
+		let top = self.neck_y.min(self.end_y);
+		let bottom = self.neck_y.max(self.end_y);
+		let row_height = diameter * 3_f64.sqrt() / 2.0;
+		let mut grains = Vec::new();
+		let mut row_bottom = bottom;
+		let mut row_num = 0_u32;
+		while row_bottom - diameter >= top && grains.len() < count as usize {
+			let half_width = self.min_half_width_between(row_bottom - diameter, row_bottom);
+			// Even rows have a grain in the middle and odd rows have two either side of it, so
+			// each row sits in the gaps of the one below.
+			let odd = row_num % 2 == 1;
+			#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+			let most = (half_width * 2.0 / diameter).floor().max(0.0) as u32;
+			let fits = if (most % 2 == 1) == odd {
+				most.saturating_sub(1)
+			} else {
+				most
+			};
+			let remaining = count - u32::try_from(grains.len()).unwrap_or(u32::MAX);
+			let in_row = fits.min(remaining);
+			// A last row that's only partly full can be off center by half a grain.
+			let offset = if odd { 0.5 } else { 0.0 };
+			let first = ((1.0 - f64::from(in_row)) / 2.0 - offset).round() + offset;
+			let first_x = first.mul_add(diameter, self.center_x);
+			let y = row_bottom - diameter / 2.0;
+			for grain in 0..in_row {
+				grains.push((f64::from(grain).mul_add(diameter, first_x), y));
+			}
+			row_bottom -= row_height;
+			row_num += 1;
+		}
+		grains
+	}
+
+	/// How many grains of a given diameter fit inside the bulb.
+	fn capacity(&self, diameter: f64) -> u32 {
+		u32::try_from(self.pack(u32::MAX, diameter).len()).unwrap_or(u32::MAX)
+	}
+
+	/// Points along one side of the bulb's outline, going from the far end to the neck.
+	fn outline(&self, side: f64) -> Vec<(f64, f64)> {
+		(0..=OUTLINE_SAMPLES)
+			.rev()
+			.map(|i| {
+				let t = f64::from(i) / f64::from(OUTLINE_SAMPLES);
+				let y = t.mul_add(self.end_y - self.neck_y, self.neck_y);
+				(side.mul_add(self.half_width_at(y), self.center_x), y)
+			})
+			.collect()
+	}
+}
+
+/// Find the largest grain diameter for which every grain fits into one bulb.
+fn grain_diameter(bulb: &Bulb, total_grains: u32) -> f64 {
+	let height = (bulb.end_y - bulb.neck_y).abs();
+	let area: f64 = (0..OUTLINE_SAMPLES)
+		.map(|i| {
+			let t = f64::from(i) / f64::from(OUTLINE_SAMPLES);
+			bulb.shape.half_width(t) * bulb.max_half_width * 2.0 * height
+				/ f64::from(OUTLINE_SAMPLES)
+		})
+		.sum();
+	let mut diameter = (area / f64::from(total_grains.max(1))).sqrt();
+	while bulb.capacity(diameter) < total_grains && diameter > f64::EPSILON {
+		diameter *= 0.98;
+	}
+	diameter
+}
+
+fn to_points(points: &[(f64, f64)]) -> String {
+	points
+		.iter()
+		.map(|&(x, y)| format!("{x},{y}"))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+pub fn render_svg(
+	life_info: &LifeInfo,
+	drawing_info: &DrawingInfoValidated,
+	style: &HourglassStyle,
+) -> Result<Document> {
+	let (grain_unit, bulb_shape) = (&style.grain_unit, &style.bulb_shape);
+	let color_primary = drawing_info.color_primary.to_hex_string();
+	let color_secondary = drawing_info.color_secondary.to_hex_string();
+	let color_stroke = drawing_info.color_stroke.to_hex_string();
+	let scale_factor = f64::from(drawing_info.scale_factor);
+
+	let bday = life_info.birthday;
+	let lifespan_years: i16 = life_info.lifespan_years.try_into()?;
+	let today = Date::today_utc();
+
+	let (total, lived, left) = match *grain_unit {
+		GrainUnit::Week => (
+			lifespan_weeks(lifespan_years),
+			weeks_lived(today, bday),
+			weeks_left(today, bday, lifespan_years),
+		),
+		GrainUnit::Month => (
+			lifespan_months(lifespan_years),
+			months_lived(today, bday),
+			months_left(today, bday, lifespan_years),
+		),
+	};
+	let total = u32::try_from(total.max(0))?;
+	// The lived and remaining counts are rounded differently, so make sure they never add up to
+	// more grains than the glass holds.
+	let lower_grains = u32::try_from(lived.clamp(0, total.try_into()?))?;
+	let upper_grains = u32::try_from(left.max(0))?.min(total - lower_grains);
+
+	let stroke_width = 2.0 * scale_factor;
+	let padding = 20.0 * scale_factor;
+	let cap_height = 12.0 * scale_factor;
+	let bulb_width = 400.0 * scale_factor;
+	let bulb_height = 300.0 * scale_factor;
+
+	let viewbox_width = padding.mul_add(2.0, bulb_width);
+	let viewbox_height = bulb_height.mul_add(2.0, padding.mul_add(2.0, cap_height * 2.0));
+
+	let mut document = init_document(viewbox_width, viewbox_height, drawing_info, life_info)?;
+
+	let center_x = viewbox_width / 2.0;
+	let neck_y = viewbox_height / 2.0;
+	let upper = Bulb {
+		shape: bulb_shape,
+		center_x,
+		max_half_width: bulb_width / 2.0 - stroke_width,
+		neck_y,
+		end_y: neck_y - bulb_height,
+	};
+	let lower = Bulb {
+		end_y: neck_y + bulb_height,
+		..upper
+	};
+
+	let mut outline = upper.outline(-1.0);
+	outline.extend(lower.outline(-1.0).into_iter().rev());
+	outline.extend(lower.outline(1.0));
+	outline.extend(upper.outline(1.0).into_iter().rev());
+	document.append(
+		Polygon::new()
+			.set("points", to_points(&outline))
+			.set("fill", "none")
+			.set("stroke", color_stroke.as_str())
+			.set("stroke-width", stroke_width),
+	);
+
+	for cap_y in [
+		upper.end_y - cap_height / 2.0,
+		lower.end_y + cap_height / 2.0,
+	] {
+		document.append(
+			Line::new()
+				.set("x1", padding)
+				.set("x2", viewbox_width - padding)
+				.set("y1", cap_y)
+				.set("y2", cap_y)
+				.set("stroke", color_stroke.as_str())
+				.set("stroke-width", cap_height),
+		);
+	}
+
+	let diameter = grain_diameter(&upper, total);
+	let grain_stroke_width = (diameter / 8.0).min(stroke_width);
+	let grain_radius = (diameter - grain_stroke_width) / 2.0;
+	let grains = upper
+		.pack(upper_grains, diameter)
+		.into_iter()
+		.map(|grain| (grain, color_secondary.as_str()))
+		.chain(
+			lower
+				.pack(lower_grains, diameter)
+				.into_iter()
+				.map(|grain| (grain, color_primary.as_str())),
+		);
+	if drawing_info.compact {
+		let mut classes = StyleClasses::default();
+		let grains: Vec<_> = grains
+			.map(|((cx, cy), fill)| {
+				Use::new()
+					.set("href", "#grain")
+					.set("x", cx)
+					.set("y", cy)
+					.set(
+						"class",
+						classes.class(fill, &color_stroke, grain_stroke_width),
+					)
+			})
+			.collect();
+		document.append(classes.to_style());
+		document.append(
+			Definitions::new().add(Circle::new().set("id", "grain").set("r", grain_radius)),
+		);
+		for grain in grains {
+			document.append(grain);
+		}
+	} else {
+		for ((cx, cy), fill) in grains {
+			document.append(
+				Circle::new()
+					.set("cx", cx)
+					.set("cy", cy)
+					.set("r", grain_radius)
+					.set("fill", fill)
+					.set("stroke", color_stroke.as_str())
+					.set("stroke-width", grain_stroke_width),
+			);
+		}
+	}
+
+	Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bulb(shape: &BulbShape) -> Bulb<'_> {
+		Bulb {
+			shape,
+			center_x: 200.0,
+			max_half_width: 200.0,
+			neck_y: 300.0,
+			end_y: 0.0,
+		}
+	}
+
+	#[test]
+	fn every_grain_fits_in_one_bulb() {
+		for shape in [BulbShape::Cone, BulbShape::Round, BulbShape::Flask] {
+			let upper = bulb(&shape);
+			for total in [1, 52 * 100, 12 * 80] {
+				let diameter = grain_diameter(&upper, total);
+				let grains = upper.pack(total, diameter);
+				assert_eq!(grains.len(), total as usize);
+				for &(x, y) in &grains {
+					assert!(y > 0.0 && y < 300.0);
+					assert!((x - 200.0).abs() <= upper.half_width_at(y));
+				}
+				for (index, &(x1, y1)) in grains.iter().enumerate() {
+					for &(x2, y2) in &grains[index + 1..] {
+						let apart = (x1 - x2).hypot(y1 - y2);
+						assert!(apart > diameter - 1e-9, "{apart} < {diameter}");
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn bulb_is_narrowest_at_the_neck() {
+		for shape in [BulbShape::Cone, BulbShape::Round, BulbShape::Flask] {
+			let neck = shape.half_width(0.0);
+			assert!((neck - NECK_RATIO).abs() < f64::EPSILON);
+			assert!((0..=10).all(|i| shape.half_width(f64::from(i) / 10.0) >= neck));
+		}
+	}
+}
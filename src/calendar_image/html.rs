@@ -0,0 +1,122 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::{death_info, LifeInfo};
+
+/// Styles for the page around the image.
+const STYLE: &str = r#"
+body {
+	margin: 0 auto;
+	max-width: 80rem;
+	padding: 1rem;
+	font-family: sans-serif;
+	display: flex;
+	flex-wrap: wrap;
+	gap: 1rem;
+	align-items: flex-start;
+}
+main {
+	flex: 1 1 40rem;
+}
+main svg {
+	width: 100%;
+	height: auto;
+}
+aside {
+	flex: 0 1 18rem;
+}
+aside .warning {
+	color: #b3261e;
+}
+aside pre {
+	font-size: 0.85rem;
+	white-space: pre-wrap;
+}
+[data-start] {
+	cursor: pointer;
+}
+[data-start].highlighted {
+	stroke: #e15759;
+	stroke-width: 4px;
+	filter: drop-shadow(0 0 3px #e15759);
+}
+#tooltip {
+	position: fixed;
+	pointer-events: none;
+	white-space: pre-line;
+	background: #222;
+	color: #fff;
+	padding: 0.25rem 0.5rem;
+	border-radius: 0.25rem;
+	font-size: 0.85rem;
+}
+"#;
+
+/// Shows the dates of whatever shape is under the mouse, and highlights shapes when clicked.
+const SCRIPT: &str = r#"
+const tooltip = document.getElementById("tooltip");
+const image = document.querySelector("main svg");
+image.addEventListener("mousemove", (event) => {
+	const cell = event.target.closest("[data-start]");
+	if (!cell) {
+		tooltip.hidden = true;
+		return;
+	}
+	const data = cell.dataset;
+	let text = `${data.start} to ${data.end}, age ${data.age}`;
+	if (data.events) {
+		text += `\n${data.events}`;
+	}
+	tooltip.textContent = text;
+	tooltip.style.left = `${event.clientX + 12}px`;
+	tooltip.style.top = `${event.clientY + 12}px`;
+	tooltip.hidden = false;
+});
+image.addEventListener("mouseleave", () => {
+	tooltip.hidden = true;
+});
+image.addEventListener("click", (event) => {
+	const cell = event.target.closest("[data-start]");
+	if (cell) {
+		cell.classList.toggle("highlighted");
+	}
+});
+"#;
+
+/// Make text safe to put inside HTML.
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+/// Write the part of the page that comes before the image, including the stats panel and any
+/// warnings about the image.
+pub fn write_start(out: &mut impl Write, life_info: &LifeInfo, warnings: &[String]) -> Result<()> {
+	let summary = death_info::summary(life_info.birthday, life_info.lifespan_years)?;
+	let report = death_info::report(life_info.birthday, life_info.lifespan_years)?;
+	let warnings = warnings
+		.iter()
+		.map(|warning| format!("<p class=\"warning\">Warning: {}</p>\n", escape(warning)))
+		.collect::<String>();
+	write!(
+		out,
+		"<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta \
+		 name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>Death \
+		 calendar</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<aside>\n<h1>Death \
+		 calendar</h1>\n<p>{}</p>\n{warnings}<pre>{}</pre>\n</aside>\n<main>\n",
+		escape(&summary),
+		escape(&report)
+	)?;
+	Ok(())
+}
+
+/// Write the part of the page that comes after the image.
+pub fn write_end(out: &mut impl Write) -> Result<()> {
+	write!(
+		out,
+		"\n</main>\n<div id=\"tooltip\" hidden></div>\n<script>{SCRIPT}</script>\n</body>\n</html>"
+	)?;
+	Ok(())
+}
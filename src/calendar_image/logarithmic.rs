@@ -1,170 +1,1011 @@
-use std::num::TryFromIntError;
+use std::{
+	f64::consts::{PI, TAU},
+	fs, iter,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use csscolorparser::Color;
 use death_calendar::days_lived;
 use gregorian::Date;
+use serde::{Deserialize, Serialize};
 use svg::{
-	node::element::{self, Line, Marker, Polyline, Text},
+	node::element::{self, Line, Marker, Path},
 	Document, Node,
 };
 
-use super::{init_document, AVERAGE_DAYS_IN_YEAR, PHI};
-use crate::{DrawingInfoValidated, LifeInfo};
+use super::{
+	font::{Label, Lettering},
+	init_document,
+	pattern::{Pattern, Patterns},
+	pixels, AVERAGE_DAYS_IN_YEAR, PHI,
+};
+use crate::{DrawingInfoValidated, LifeInfo, LogStyle};
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogScale {
+	/// Gives the early years more room, without going as far as a logarithm
+	Classic,
+	/// Every year is as wide as every other
+	Linear,
+	/// The logarithm of your age, counted from the day you were born
+	Log,
+	/// Your share of your lifespan, raised to the power of `--exponent`
+	Power,
+	/// Each year is as wide as it feels, which is one over your age once you're older than
+	/// `--perceived-start-age`
+	Perceived,
+}
+
+/// How many pieces each of the past and future are split into when they're drawn as gradients.
+const GRADIENT_PIECES: u32 = 60;
+
+/// Where each age goes along the line of a logarithmic calendar.
+struct Scale<'a> {
+	kind: &'a LogScale,
+	lifespan: f64,
+	exponent: f64,
+	perceived_start_age: f64,
+}
+
+impl Scale<'_> {
+	/// How long a life feels up to an age, counting the years before `perceived_start_age` as
+	/// all feeling as long as that one.
+	fn felt(&self, years: f64) -> f64 {
+		let start = self.perceived_start_age;
+		if years <= start {
+			years / start
+		} else {
+			1.0 + (years / start).ln()
+		}
+	}
+
+	/// How far along the line an age is, from 0 at birth to 1 at the end of the lifespan.
+	fn position(&self, years: f64) -> f64 {
+		let lifespan = self.lifespan;
+		match *self.kind {
+			LogScale::Classic => {
+				1.0 - (f64::powf(lifespan + 1.0, 1.0 - (years / lifespan)) - 1.0) / lifespan
+			},
+			LogScale::Linear => years / lifespan,
+			LogScale::Log => years.ln_1p() / lifespan.ln_1p(),
+			LogScale::Power => (years / lifespan).powf(self.exponent),
+			LogScale::Perceived => self.felt(years) / self.felt(lifespan),
+		}
+	}
+
+	/// The age at a position along the line, undoing `position`.
+	fn years_at(&self, position: f64) -> f64 {
+		let lifespan = self.lifespan;
+		match *self.kind {
+			LogScale::Classic => {
+				lifespan * (1.0 - (lifespan.mul_add(1.0 - position, 1.0)).ln() / lifespan.ln_1p())
+			},
+			LogScale::Linear => position * lifespan,
+			LogScale::Log => (position * lifespan.ln_1p()).exp_m1(),
+			LogScale::Power => lifespan * position.powf(self.exponent.recip()),
+			LogScale::Perceived => {
+				let felt = position * self.felt(lifespan);
+				if felt <= 1.0 {
+					felt * self.perceived_start_age
+				} else {
+					self.perceived_start_age * (felt - 1.0).exp()
+				}
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLayout {
+	/// Left to right, with the arrow coming down from above
+	Horizontal,
+	/// Top to bottom, with the arrow coming in from the right, which suits phone wallpapers
+	Vertical,
+	/// Clockwise around most of a circle from the top, with the arrow coming in from outside
+	Radial,
+}
+
+#[derive(Debug, Default, Clone, Copy, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrowHead {
+	/// A filled triangle
+	#[default]
+	Triangle,
+	/// Two lines meeting at a point
+	Open,
+	/// A round dot
+	Dot,
+	/// Just the line
+	Plain,
+}
+
+/// How far round the circle a radial calendar goes, leaving a gap between its start and end.
+const SWEEP: f64 = TAU * 5.0 / 6.0;
+
+/// Where the line of a calendar goes in the image.
+///
+/// Things are placed by how far `along` the line they are, and how far `out` from it they are
+/// towards the arrow. Labels hang off the other side, where `out` is negative.
+struct Track<'a> {
+	layout: &'a LogLayout,
+	/// Start of the line, or the center of the circle for radial calendars
+	origin: (f64, f64),
+	length: f64,
+	/// Radius of the line for radial calendars
+	radius: f64,
+}
+
+impl Track<'_> {
+	/// Angle of a point on a radial calendar, clockwise from straight up.
+	fn angle(&self, along: f64) -> f64 {
+		along / self.length * SWEEP
+	}
+
+	fn point(&self, along: f64, out: f64) -> (f64, f64) {
+		let (x, y) = self.origin;
+		match *self.layout {
+			LogLayout::Horizontal => (x + along, y - out),
+			LogLayout::Vertical => (x + out, y + along),
+			LogLayout::Radial => {
+				let (angle, radius) = (self.angle(along), self.radius + out);
+				(
+					radius.mul_add(angle.sin(), x),
+					(-radius).mul_add(angle.cos(), y),
+				)
+			},
+		}
+	}
+
+	/// Which way is out from the line, and which way the line is going, as unit vectors.
+	fn directions(&self, along: f64) -> ((f64, f64), (f64, f64)) {
+		match *self.layout {
+			LogLayout::Horizontal => ((0.0, -1.0), (1.0, 0.0)),
+			LogLayout::Vertical => ((1.0, 0.0), (0.0, 1.0)),
+			LogLayout::Radial => {
+				let (sin, cos) = self.angle(along).sin_cos();
+				((sin, -cos), (cos, sin))
+			},
+		}
+	}
+
+	/// How much room some text takes up along the line, and out from it.
+	fn text_extent(&self, along: f64, height: f64, width: f64) -> (f64, f64) {
+		let ((out_x, out_y), (along_x, along_y)) = self.directions(along);
+		(
+			width.mul_add(along_x.abs(), height * along_y.abs()),
+			width.mul_add(out_x.abs(), height * out_y.abs()),
+		)
+	}
 
-fn position_from_0_to_1(lifespan: u16, inc: f64) -> f64 {
-	let lifespanf = f64::from(lifespan);
-	1_f64 - (f64::powf(lifespanf + 1_f64, 1_f64 - (inc / lifespanf)) - 1_f64) / lifespanf
+	/// Path following the line from one point along it to another, `out` from it.
+	fn segment(&self, from: f64, to: f64, out: f64) -> String {
+		let ((x1, y1), (x2, y2)) = (self.point(from, out), self.point(to, out));
+		if let LogLayout::Radial = *self.layout {
+			let radius = self.radius + out;
+			let large_arc = u8::from(self.angle(to - from) > PI);
+			format!("M {x1} {y1} A {radius} {radius} 0 {large_arc} 1 {x2} {y2}")
+		} else {
+			format!("M {x1} {y1} L {x2} {y2}")
+		}
+	}
+
+	/// Outline of the area between two distances out from the line, from one point along it to
+	/// another.
+	fn band(&self, from: f64, to: f64, inner: f64, outer: f64) -> String {
+		let (x1, y1) = self.point(from, outer);
+		let (x2, y2) = self.point(to, outer);
+		let (x3, y3) = self.point(to, inner);
+		let (x4, y4) = self.point(from, inner);
+		if let LogLayout::Radial = *self.layout {
+			let (outer, inner) = (self.radius + outer, self.radius + inner);
+			let large_arc = u8::from(self.angle(to - from) > PI);
+			format!(
+				"M {x1} {y1} A {outer} {outer} 0 {large_arc} 1 {x2} {y2} L {x3} {y3} A {inner} \
+				 {inner} 0 {large_arc} 0 {x4} {y4} Z"
+			)
+		} else {
+			format!("M {x1} {y1} L {x2} {y2} L {x3} {y3} L {x4} {y4} Z")
+		}
+	}
+
+	/// Where to anchor some text so its nearest edge is `out` from the line, and how to anchor
+	/// it. Text always reads left to right, so it's lined up by whichever of its sides faces the
+	/// line.
+	fn text_anchor(
+		&self,
+		along: f64,
+		out: f64,
+		height: f64,
+		width: f64,
+	) -> ((f64, f64), &'static str) {
+		let ((out_x, out_y), _) = self.directions(along);
+		let side = if out < 0.0 { -1.0 } else { 1.0 };
+		let away_x = out_x * side;
+		let (anchor, depth) = if away_x > 0.5 {
+			("start", height * out_y.abs() / 2.0)
+		} else if away_x < -0.5 {
+			("end", height * out_y.abs() / 2.0)
+		} else {
+			("middle", self.text_extent(along, height, width).1 / 2.0)
+		};
+		(self.point(along, depth.mul_add(side, out)), anchor)
+	}
+
+	fn text(
+		&self,
+		lettering: &Lettering,
+		content: &str,
+		along: f64,
+		out: f64,
+		height: f64,
+	) -> Result<Label> {
+		let width = lettering.width(content, height)?;
+		let (point, anchor) = self.text_anchor(along, out, height, width);
+		lettering.text(content, point, height, Some(anchor), true)
+	}
 }
 
-fn make_arrowhead(color_primary: &str) -> Marker {
+/// The end of an arrow, or nothing for arrows that are just a line.
+fn make_arrowhead(id: &str, arrow: ArrowHead, color: &str) -> Option<Marker> {
 	let scale = 4.0;
 	let arrowhead_width = PHI * scale;
 	let arrowhead_height = 1.0 * scale;
 	let arrowhead_midpoint = arrowhead_height / 2.0;
-	Marker::new()
-		.set("id", "arrowhead")
+	let marker = Marker::new()
+		.set("id", id)
 		.set("markerWidth", arrowhead_width)
 		.set("markerHeight", arrowhead_height)
 		.set("refX", 0_u8)
 		.set("refY", arrowhead_midpoint)
 		.set("orient", "auto")
-		.set("fill", color_primary)
-		.add(element::Polygon::new().set(
+		.set("fill", color);
+	match arrow {
+		ArrowHead::Triangle => Some(marker.add(element::Polygon::new().set(
 			"points",
 			format!("0 0, {arrowhead_width}, {arrowhead_midpoint}, 0 {arrowhead_height}"),
-		))
-}
-
-fn str_to_charcount(s: &str) -> Result<u32, TryFromIntError> {
-	u32::try_from(s.chars().count())
-}
-
-fn num_to_charcount(num: u32) -> Result<u32> {
-	Ok(str_to_charcount(&num.to_string())?)
+		))),
+		ArrowHead::Open => Some(
+			marker.add(
+				element::Polyline::new()
+					.set(
+						"points",
+						format!(
+							"0 0, {arrowhead_width} {arrowhead_midpoint}, 0 {arrowhead_height}"
+						),
+					)
+					.set("fill", "none")
+					.set("stroke", color)
+					.set("stroke-width", 1),
+			),
+		),
+		ArrowHead::Dot => Some(
+			marker.add(
+				element::Circle::new()
+					.set("cx", arrowhead_midpoint)
+					.set("cy", arrowhead_midpoint)
+					.set("r", arrowhead_midpoint),
+			),
+		),
+		ArrowHead::Plain => None,
+	}
 }
 
 pub fn render_svg(
 	common_args: &LifeInfo,
 	drawing_info: &DrawingInfoValidated,
-	width_to_height_ratio: f64,
+	style: &LogStyle,
 ) -> Result<Document> {
+	let width_to_height_ratio = style.width_height_ratio;
 	let color_primary = drawing_info.color_primary.to_hex_string();
-	let color_secondary = drawing_info.color_secondary.to_hex_string();
+	let color_background = drawing_info.color_background.to_hex_string();
+	let color_stroke = drawing_info.color_stroke.to_hex_string();
+	let color_text = drawing_info.color_text.to_hex_string();
+	let color_accent = drawing_info.color_accent.to_hex_string();
 	let scale_factor = drawing_info.scale_factor;
 
 	let bday = common_args.birthday;
 	let lifespan_years = common_args.lifespan_years;
 
-	// I'm displaying fonts with length defined as pixels.
-	let font_size_pixels: f64 = (24 * scale_factor).into();
-	// The length of a whole number must be a multiple of the pixel length of digits
-	let text_size =
-		font_size_pixels * <u32 as Into<f64>>::into(num_to_charcount(lifespan_years.into())?) / 2.0;
+	if style.exponent <= 0.0 || style.perceived_start_age <= 0.0 {
+		bail!("The exponent and the perceived start age both have to be more than 0");
+	}
+	if width_to_height_ratio < 1.0 {
+		bail!(
+			"The line of a logarithmic calendar has to be longer than the calendar is across. Try \
+			 `--layout vertical` for a tall calendar."
+		);
+	}
+	let scale = Scale {
+		kind: &style.scale,
+		lifespan: lifespan_years.into(),
+		exponent: style.exponent,
+		perceived_start_age: style.perceived_start_age,
+	};
+
+	if style.text_to_path && style.font_file.is_none() {
+		bail!(
+			"Text can only be turned into paths with a font to take the letters from, given with \
+			 `--font-file`"
+		);
+	}
+	let font_data = style
+		.font_file
+		.as_ref()
+		.map(|path| fs::read(path).with_context(|| format!("Could not read {}", path.display())))
+		.transpose()?;
+	let lettering = Lettering::new(
+		style.font_family.as_deref(),
+		font_data.as_deref(),
+		style.text_to_path,
+	)?;
 
-	let inner_width = 6.0 * width_to_height_ratio * font_size_pixels;
-	let inner_height = 6.0 * font_size_pixels;
+	// I'm displaying fonts with length defined as pixels.
+	let font_size_pixels = f64::from(pixels(24.0 * f64::from(scale_factor))?);
+	let event_font_size = font_size_pixels * 0.75;
+	let text_width = |text: &str| lettering.width(text, font_size_pixels);
+	let event_text_width = |text: &str| lettering.width(text, event_font_size);
+	let lifespan_label_width = text_width(&lifespan_years.to_string())?;
 
+	let length = 6.0 * width_to_height_ratio * font_size_pixels;
 	let stroke_width = font_size_pixels / 12.0;
-	let padding_y = (text_size / 0.8_f64) / 2.0;
+	// Room around the edges of the image, so labels at the ends of the line aren't cut off
+	let padding = (lifespan_label_width / 0.8_f64) / 2.0;
+	let label_gap = font_size_pixels / 4.0;
+
+	// Arrows come in from outside the ticks, with their labels beyond them.
+	let tick_height = font_size_pixels;
+	let arrow_tip = tick_height + font_size_pixels;
+	let arrow_tail = tick_height + font_size_pixels * 3.0;
+	let label_out = arrow_tail + label_gap;
+
+	let position_along = |inc: f64| -> f64 { scale.position(inc) * length };
 
-	let label = "You Are Here";
-	let label_width = f64::from(str_to_charcount(label)?);
-	let padding_x = ((font_size_pixels / 2.0) * label_width) / 2.0;
+	// How old someone is on a day, limited to their lifetime
+	let years_at = |date: Date| {
+		(f64::from(days_lived(date, bday)) / AVERAGE_DAYS_IN_YEAR).clamp(0.0, lifespan_years.into())
+	};
 
-	let viewbox_width = padding_x.mul_add(2.0, inner_width);
-	let viewbox_height = padding_y.mul_add(2.0, inner_height);
+	let years_lived_so_far = f64::from(days_lived(Date::today_utc(), bday)) / AVERAGE_DAYS_IN_YEAR;
+	let today_along = position_along(years_lived_so_far);
 
-	let mut document = init_document(viewbox_width, viewbox_height, &color_secondary);
+	// Today's arrow comes first, followed by the markers that fall within a lifetime.
+	let markers = drawing_info.markers.iter().filter(|marker| {
+		let years = f64::from(days_lived(marker.date, bday)) / AVERAGE_DAYS_IN_YEAR;
+		(0.0..=lifespan_years.into()).contains(&years)
+	});
+	let pointers = iter::once((
+		style.label.as_str(),
+		today_along,
+		color_accent.clone(),
+		style.arrow,
+	))
+	.chain(markers.map(|marker| {
+		(
+			marker.name.as_str(),
+			position_along(years_at(marker.date)),
+			marker.color.to_hex_string(),
+			marker.arrow,
+		)
+	}))
+	.map(|(name, along, color, arrow)| Ok((name, along, color, arrow, text_width(name)?)))
+	.collect::<Result<Vec<_>>>()?;
+	let label_width = pointers
+		.iter()
+		.map(|&(.., width)| width)
+		.fold(0.0, f64::max);
 
-	let position_within_inner_viewbox = |inc: f64| -> f64 {
-		position_from_0_to_1(lifespan_years, inc).mul_add(inner_width, padding_x)
+	// The track's position only matters for where things end up, not how much room they take,
+	// so everything is measured on a track at the origin first.
+	let mut track = Track {
+		layout: &style.layout,
+		origin: (0.0, 0.0),
+		length,
+		radius: length / SWEEP,
 	};
 
-	let arrow_length = font_size_pixels * 3.0;
-	let arrowhead = make_arrowhead(&color_primary);
-	document.append(arrowhead);
+	// Year labels hang off the line, with events below them on as many rows as it takes to keep
+	// them apart.
+	let year_label_depth = match style.layout {
+		LogLayout::Horizontal => font_size_pixels,
+		LogLayout::Vertical | LogLayout::Radial => lifespan_label_width.max(font_size_pixels),
+	};
+	let events_top = label_gap.mul_add(2.0, year_label_depth);
+	let events: Vec<_> = drawing_info
+		.events
+		.iter()
+		.filter_map(|event| {
+			let start = position_along(years_at(event.start));
+			let end = position_along(years_at(event.end));
+			// Skip events that happen entirely outside of a lifetime.
+			(event.end > bday && start < length).then_some((event, start, end))
+		})
+		.collect();
+	let event_label_extents = events
+		.iter()
+		.map(|&(event, start, _)| {
+			let width = event_text_width(&event.name)?;
+			Ok(track.text_extent(start, event_font_size, width))
+		})
+		.collect::<Result<Vec<_>>>()?;
+	let event_rows = stack_labels(
+		&events
+			.iter()
+			.zip(&event_label_extents)
+			.map(|(&(_, start, _), &(along, _))| (start - along / 2.0, start + along / 2.0))
+			.collect::<Vec<_>>(),
+		event_font_size,
+	);
+	let event_row_count = event_rows.iter().max().map_or(0, |row| row + 1);
+	// Each row is as deep as its deepest label.
+	let mut event_row_depths = vec![0.0_f64; event_row_count];
+	for (&row, &(_, depth)) in event_rows.iter().zip(&event_label_extents) {
+		event_row_depths[row] = event_row_depths[row].max(depth + event_font_size / 2.0);
+	}
+	let event_row_tops = event_row_depths
+		.iter()
+		.scan(events_top, |top, depth| {
+			let row_top = *top;
+			*top += depth;
+			Some(row_top)
+		})
+		.collect::<Vec<_>>();
+	let mut inside = events_top + event_row_depths.iter().sum::<f64>();
 
-	let years_lived_so_far = f64::from(days_lived(Date::today_utc(), bday)) / AVERAGE_DAYS_IN_YEAR;
-	let timeline_pos_today = position_within_inner_viewbox(years_lived_so_far);
-
-	let today_arrow = Line::new()
-		.set("x1", timeline_pos_today)
-		.set("x2", timeline_pos_today)
-		.set("y1", padding_y + font_size_pixels)
-		.set("y2", padding_y + arrow_length)
-		.set("marker-end", "url(#arrowhead)")
-		.set("stroke-width", stroke_width)
-		.set("stroke", color_primary.as_str());
-	document.append(today_arrow);
-
-	// Baseline
-	let baseline_height = inner_height;
-	let top_of_line_height = font_size_pixels + padding_y + arrow_length;
-	let initial_x = position_within_inner_viewbox(0_f64);
-	let final_x = position_within_inner_viewbox(lifespan_years.into());
+	// Then how long has been lived and how long is left, across from today but clear of the
+	// events' lines.
+	let remaining = if style.show_remaining {
+		let left = (f64::from(lifespan_years) - years_lived_so_far).max(0.0);
+		let text = style
+			.remaining_text
+			.replace("{lived}", &format!("{years_lived_so_far:.1}"))
+			.replace("{left}", &format!("{left:.1}"));
+		let width = event_text_width(&text)?;
+		let (extent, depth) = track.text_extent(today_along, event_font_size, width);
+		let along = today_along.clamp(extent / 2.0, (length - extent / 2.0).max(extent / 2.0));
+		let out = -inside;
+		inside += depth + label_gap;
+		Some((text, along, out))
+	} else {
+		None
+	};
+
+	// Labels of arrows are stacked outwards the same way events are stacked inwards.
+	let pointer_label_extents = pointers
+		.iter()
+		.map(|&(_, along, _, _, width)| track.text_extent(along, font_size_pixels, width))
+		.collect::<Vec<_>>();
+	let pointer_rows = stack_labels(
+		&pointers
+			.iter()
+			.zip(&pointer_label_extents)
+			.map(|(&(_, along, ..), &(extent, _))| (along - extent / 2.0, along + extent / 2.0))
+			.collect::<Vec<_>>(),
+		font_size_pixels,
+	);
+	let pointer_row_count = pointer_rows.iter().max().map_or(0, |row| row + 1);
+	let mut pointer_row_depths = vec![0.0_f64; pointer_row_count];
+	for (&row, &(_, depth)) in pointer_rows.iter().zip(&pointer_label_extents) {
+		pointer_row_depths[row] = pointer_row_depths[row].max(depth + label_gap);
+	}
+	let pointer_row_outs = pointer_row_depths
+		.iter()
+		.scan(label_out, |out, depth| {
+			let row_out = *out;
+			*out += depth;
+			Some(row_out)
+		})
+		.collect::<Vec<_>>();
+	let outside = label_out + pointer_row_depths.iter().sum::<f64>() - label_gap;
+	let (viewbox_width, content_height) = match style.layout {
+		LogLayout::Horizontal => {
+			let padding_x = label_width / 2.0;
+			track.origin = (padding_x, padding + outside);
+			(
+				padding_x.mul_add(2.0, length),
+				padding.mul_add(2.0, outside + inside),
+			)
+		},
+		LogLayout::Vertical => {
+			track.origin = (padding + inside, font_size_pixels);
+			(
+				padding.mul_add(2.0, inside + outside),
+				font_size_pixels.mul_add(2.0, length),
+			)
+		},
+		LogLayout::Radial => {
+			track.radius = track.radius.max(inside + font_size_pixels);
+			// The circle, with the arrows' labels sticking out of it
+			let ring = track.radius + tick_height;
+			let (mut left, mut right, mut top, mut bottom) = (-ring, ring, -ring, ring);
+			for (&(_, along, _, _, width), &row) in pointers.iter().zip(&pointer_rows) {
+				let ((x, y), anchor) =
+					track.text_anchor(along, pointer_row_outs[row], font_size_pixels, width);
+				let label_left = match anchor {
+					"start" => x,
+					"end" => x - width,
+					_ => x - width / 2.0,
+				};
+				left = left.min(label_left);
+				right = right.max(label_left + width);
+				top = top.min(y - font_size_pixels / 2.0);
+				bottom = bottom.max(y + font_size_pixels / 2.0);
+			}
+			track.origin = (padding - left, padding - top);
+			(
+				padding.mul_add(2.0, right - left),
+				padding.mul_add(2.0, bottom - top),
+			)
+		},
+	};
+
+	// Phases are listed below everything else, one to a row.
+	let legend_x = match style.layout {
+		LogLayout::Horizontal => label_width / 2.0,
+		LogLayout::Vertical | LogLayout::Radial => padding,
+	};
+	let event_row_height = event_font_size * 1.5;
+	let legend_rows = u32::try_from(drawing_info.phases.len())?;
+	let viewbox_height = event_row_height.mul_add(f64::from(legend_rows), content_height);
+
+	let mut document = init_document(viewbox_width, viewbox_height, drawing_info, common_args)?;
+
+	let line = |from: (f64, f64), to: (f64, f64), width: f64, color: &str| {
+		Line::new()
+			.set("x1", from.0)
+			.set("y1", from.1)
+			.set("x2", to.0)
+			.set("y2", to.1)
+			.set("stroke-width", width)
+			.set("stroke", color)
+	};
+	let segment = |from: f64, to: f64, out: f64, width: f64, color: &str| {
+		Path::new()
+			.set("d", track.segment(from, to, out))
+			.set("fill", "none")
+			.set("stroke-width", width)
+			.set("stroke", color)
+	};
+
+	for (index, (&(_, along, ref color, arrow, _), &row)) in
+		pointers.iter().zip(&pointer_rows).enumerate()
+	{
+		// Today's arrowhead keeps the name it's always had.
+		let id = if index == 0 {
+			"arrowhead".to_owned()
+		} else {
+			format!("arrowhead-{index}")
+		};
+		let mut arrow_line = line(
+			track.point(along, pointer_row_outs[row] - label_gap),
+			track.point(along, arrow_tip),
+			stroke_width,
+			color,
+		);
+		if let Some(arrowhead) = make_arrowhead(&id, arrow, color) {
+			document.append(arrowhead);
+			arrow_line = arrow_line.set("marker-end", format!("url(#{id})"));
+		}
+		document.append(arrow_line);
+	}
+
+	// The part of the line already lived is shaded in, up to the tops of the ticks.
+	let lived_along = today_along.clamp(0.0, length);
+	if lived_along > 0.0 {
+		document.append(
+			Path::new()
+				.set("d", track.band(0.0, lived_along, 0.0, tick_height))
+				.set("fill", color_primary.as_str())
+				.set("fill-opacity", 0.15),
+		);
+	}
+
+	// Baseline, with posts at each end
+	let (start_x, start_y) = track.point(0.0, tick_height);
+	let (end_x, end_y) = track.point(length, tick_height);
+	let baseline = track.segment(0.0, length, 0.0);
 	document.append(
-		Polyline::new()
+		Path::new()
 			.set(
-				"points",
+				"d",
 				format!(
-					"{initial_x} {top_of_line_height},{initial_x} {baseline_height},{final_x} \
-					 {baseline_height},{final_x} {top_of_line_height}"
+					"M {start_x} {start_y} L{} L {end_x} {end_y}",
+					baseline.trim_start_matches('M')
 				),
 			)
 			.set("fill", "none")
 			.set("stroke-width", stroke_width)
-			.set("stroke", color_primary.as_str()),
+			.set("stroke", color_stroke.as_str()),
 	);
 
-	document.append(
-		Text::new(label)
-			.set("x", timeline_pos_today)
-			.set("y", padding_y + (font_size_pixels / 2.0))
-			.set("stroke", color_primary.as_str())
-			.set("fill", color_primary.as_str())
-			.set("font-size", format!("{font_size_pixels}px"))
-			.set("text-anchor", "middle"),
+	// Gradients and patterns fill a band along the baseline. Gradients are drawn as short pieces
+	// of it, each colored by the age at its middle.
+	let phase_band_width = stroke_width * 4.0;
+	let mut patterns = Patterns::new(
+		stroke_width * 2.0,
+		&drawing_info.color_stroke,
+		&drawing_info.color_background,
 	);
+	let parts = [
+		(
+			0.0,
+			lived_along,
+			true,
+			drawing_info.past_pattern,
+			&drawing_info.color_primary,
+		),
+		(
+			lived_along,
+			length,
+			false,
+			drawing_info.future_pattern,
+			&drawing_info.color_secondary,
+		),
+	];
+	for (from, to, lived, pattern, ground) in parts.into_iter().filter(|&(from, to, ..)| to > from)
+	{
+		let mut band = |start: f64, end: f64, ground: &Color| {
+			document.append(
+				Path::new()
+					.set("d", track.band(start, end, 0.0, phase_band_width))
+					.set("fill", patterns.contrasting_fill(pattern, ground)),
+			);
+		};
+		match drawing_info.gradient {
+			Some(ref gradient) => {
+				let years_left = f64::from(lifespan_years) - years_lived_so_far;
+				let piece_length = (to - from) / f64::from(GRADIENT_PIECES);
+				for piece in 0..GRADIENT_PIECES {
+					let start = piece_length.mul_add(f64::from(piece), from);
+					let years = scale.years_at((start + piece_length / 2.0) / length);
+					let color = if lived {
+						gradient.past_at(years / years_lived_so_far)
+					} else {
+						gradient.future_at((years - years_lived_so_far) / years_left)
+					};
+					band(start, start + piece_length, &color);
+				}
+			},
+			None if pattern != Pattern::Solid => band(from, to, ground),
+			None => {},
+		}
+	}
+
+	// Phases shade the part of the baseline they cover, with the part already lived in their past
+	// color.
+	for phase in &drawing_info.phases {
+		let start = position_along(years_at(phase.start));
+		let end = position_along(years_at(phase.end));
+		let today = today_along.clamp(start, end);
+		let segments = [
+			(start, today, &phase.color_past),
+			(today, end, &phase.color_future),
+		];
+		for (from, to, color) in segments {
+			if to > from {
+				document.append(segment(
+					from,
+					to,
+					phase_band_width / 2.0,
+					phase_band_width,
+					&color.to_hex_string(),
+				));
+			}
+		}
+	}
 
-	let mut previous_x = f64::MIN;
+	for ((&(name, along, ref color, ..), &row), &(extent, _)) in pointers
+		.iter()
+		.zip(&pointer_rows)
+		.zip(&pointer_label_extents)
+	{
+		let text = track.text(
+			&lettering,
+			name,
+			along,
+			pointer_row_outs[row],
+			font_size_pixels,
+		)?;
+		// Arrows going to labels further out can't help crossing this one, so it's drawn over
+		// them with a border the color of the background.
+		let crossed = pointers
+			.iter()
+			.zip(&pointer_rows)
+			.any(|(&(_, other, ..), &other_row)| {
+				other_row > row && (other - along).abs() < extent / 2.0
+			});
+		document.append(if crossed {
+			text.set("stroke", color_background.as_str())
+				.set("stroke-width", stroke_width * 4.0)
+				.set("paint-order", "stroke")
+				.set("fill", color.as_str())
+		} else {
+			text.set("stroke", color.as_str())
+				.set("fill", color.as_str())
+		});
+	}
 
+	// Years are labelled wherever there's room between them, always including the first and
+	// last.
+	let year_label = |year: u16| -> Result<(Label, (f64, f64), f64)> {
+		let along = position_along(f64::from(year));
+		let width = text_width(&year.to_string())?;
+		let (point, _) = track.text_anchor(along, -label_gap, font_size_pixels, width);
+		let gap_size = track.text_extent(along, font_size_pixels, width).0 * 2.0 / PHI;
+		let text = track
+			.text(
+				&lettering,
+				&year.to_string(),
+				along,
+				-label_gap,
+				font_size_pixels,
+			)?
+			.set("stroke", color_text.as_str())
+			.set("fill", color_text.as_str());
+		Ok((text, point, gap_size))
+	};
+	let distance = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| (x2 - x1).hypot(y2 - y1);
+	let (_, last_point, _) = year_label(lifespan_years)?;
+	// Months get shorter ticks in years with room for them, with the quarters a bit taller, and
+	// years without room for months can still have quarters.
+	let mut sub_ticks = String::new();
+	let mut add_sub_ticks = |year: u16| {
+		let start = f64::from(year);
+		for (parts, quarter) in [(12_u8, 3), (4, 1)] {
+			let alongs = (0..=parts)
+				.map(|part| position_along(start + f64::from(part) / f64::from(parts)))
+				.collect::<Vec<_>>();
+			if alongs.windows(2).all(|pair| pair[1] - pair[0] >= label_gap) {
+				for (part, &along) in (0..parts).zip(&alongs).skip(1) {
+					let height = if part % quarter == 0 {
+						tick_height / 2.0
+					} else {
+						tick_height / 4.0
+					};
+					let ((x1, y1), (x2, y2)) =
+						(track.point(along, 0.0), track.point(along, height));
+					sub_ticks.push_str(&format!("M {x1} {y1} L {x2} {y2} "));
+				}
+				break;
+			}
+		}
+	};
+	let mut previous_point = None;
 	for year_num in 0..=lifespan_years {
-		let current_x = position_within_inner_viewbox(f64::from(year_num));
-		let d_prev_curr = current_x - previous_x;
-		let d_curr_last = final_x - current_x;
-
-		let year_digits = num_to_charcount(year_num.into())?;
-
-		let gap_size = (font_size_pixels * f64::from(year_digits)) / PHI;
-
-		let there_is_enough_space_between_lines = d_prev_curr >= gap_size && d_curr_last > gap_size;
-
-		let year_label_text = Text::new(year_num.to_string())
-			.set("x", current_x)
-			.set("y", baseline_height + font_size_pixels)
-			.set("stroke", color_primary.as_str())
-			.set("fill", color_primary.as_str())
-			.set("font-size", format!("{font_size_pixels}px"))
-			.set("text-anchor", "middle");
-		let year_label_line = Line::new()
-			.set("x1", current_x)
-			.set("y1", baseline_height)
-			.set("x2", current_x)
-			.set("y2", font_size_pixels + padding_y + arrow_length)
-			.set("stroke-width", stroke_width)
-			.set("stroke", color_primary.as_str());
+		let (text, point, gap_size) = year_label(year_num)?;
+		let there_is_enough_space_between_lines = previous_point
+			.is_none_or(|previous| distance(previous, point) >= gap_size)
+			&& distance(point, last_point) > gap_size;
 
 		if year_num == 0 || year_num == lifespan_years {
-			document.append(year_label_text);
-			previous_x = current_x;
+			document.append(text);
+			previous_point = Some(point);
+			if year_num == 0 {
+				add_sub_ticks(year_num);
+			}
 		} else if there_is_enough_space_between_lines {
-			document.append(year_label_line);
-			document.append(year_label_text);
-			previous_x = current_x;
+			let along = position_along(f64::from(year_num));
+			document.append(line(
+				track.point(along, 0.0),
+				track.point(along, tick_height),
+				stroke_width,
+				&color_stroke,
+			));
+			document.append(text);
+			previous_point = Some(point);
+			add_sub_ticks(year_num);
 		} else {
 			// Do nothing
 		}
 	}
+	if !sub_ticks.is_empty() {
+		document.append(
+			Path::new()
+				.set("d", sub_ticks.trim_end())
+				.set("fill", "none")
+				.set("stroke-width", stroke_width / 2.0)
+				.set("stroke", color_stroke.as_str()),
+		);
+	}
+
+	for (&(event, start, end), row) in events.iter().zip(event_rows) {
+		let color = event.color.to_hex_string();
+		let row_top = event_row_tops[row];
+		if end - start > stroke_width && drawing_info.event_pattern != Pattern::Solid {
+			let fill = patterns.fill(
+				drawing_info.event_pattern,
+				&drawing_info.color_background,
+				&event.color,
+			);
+			document.append(
+				Path::new()
+					.set(
+						"d",
+						track.band(start, end, -stroke_width * 2.0, stroke_width * 2.0),
+					)
+					.set("fill", fill),
+			);
+		} else if end - start > stroke_width {
+			document.append(
+				segment(start, end, 0.0, stroke_width * 4.0, &color).set("stroke-opacity", 0.6),
+			);
+		}
+		document.append(line(
+			track.point(start, 0.0),
+			track.point(start, -row_top),
+			stroke_width,
+			&color,
+		));
+		document.append(
+			track
+				.text(&lettering, &event.name, start, -row_top, event_font_size)?
+				.set("fill", color.as_str()),
+		);
+	}
+
+	if let Some((text, along, out)) = remaining {
+		document.append(
+			track
+				.text(&lettering, &text, along, out, event_font_size)?
+				.set("fill", color_text.as_str()),
+		);
+	}
+
+	for (row, phase) in (0_u32..).zip(&drawing_info.phases) {
+		let y = event_row_height.mul_add(f64::from(row), content_height);
+		for (index, color) in (0_u32..).zip([&phase.color_past, &phase.color_future]) {
+			document.append(
+				element::Rectangle::new()
+					.set(
+						"x",
+						(event_font_size * 1.5).mul_add(f64::from(index), legend_x),
+					)
+					.set("y", y - event_font_size)
+					.set("width", event_font_size)
+					.set("height", event_font_size)
+					.set("fill", color.to_hex_string())
+					.set("stroke-width", stroke_width)
+					.set("stroke", color_stroke.as_str()),
+			);
+		}
+		document.append(
+			lettering
+				.text(
+					&phase.name,
+					(event_font_size.mul_add(3.0, legend_x), y),
+					event_font_size,
+					None,
+					false,
+				)?
+				.set("fill", color_text.as_str()),
+		);
+	}
+
+	if !patterns.is_empty() {
+		document.append(patterns.to_definitions());
+	}
 
 	Ok(document)
 }
+
+/// Put labels on rows so that none of them overlap, using as few rows as possible.
+///
+/// Each label is given as its left and right edges. Returns the row for each label.
+fn stack_labels(spans: &[(f64, f64)], gap: f64) -> Vec<usize> {
+	let mut order: Vec<usize> = (0..spans.len()).collect();
+	order.sort_by(|&a, &b| spans[a].0.total_cmp(&spans[b].0));
+	// The right edge of the last label put on each row so far
+	let mut row_ends: Vec<f64> = Vec::new();
+	let mut rows = vec![0; spans.len()];
+	for index in order {
+		let (left, right) = spans[index];
+		let row = row_ends
+			.iter()
+			.position(|&end| left - end >= gap)
+			.unwrap_or(row_ends.len());
+		if row == row_ends.len() {
+			row_ends.push(right);
+		} else {
+			row_ends[row] = right;
+		}
+		rows[index] = row;
+	}
+	rows
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn every_scale_can_be_undone() {
+		for kind in [
+			LogScale::Classic,
+			LogScale::Linear,
+			LogScale::Log,
+			LogScale::Power,
+			LogScale::Perceived,
+		] {
+			let scale = Scale {
+				kind: &kind,
+				lifespan: 90.0,
+				exponent: 0.4,
+				perceived_start_age: 3.0,
+			};
+			assert!(scale.position(0.0).abs() < 1e-9, "{kind:?}");
+			assert!((scale.position(90.0) - 1.0).abs() < 1e-9, "{kind:?}");
+			for years in [0.5, 2.0, 3.0, 17.25, 45.0, 89.0] {
+				let position = scale.position(years);
+				assert!((0.0..=1.0).contains(&position), "{kind:?}");
+				assert!(
+					(scale.years_at(position) - years).abs() < 1e-9,
+					"{kind:?} at {years}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn perceived_years_shrink_with_age() {
+		let scale = Scale {
+			kind: &LogScale::Perceived,
+			lifespan: 80.0,
+			exponent: 1.0,
+			perceived_start_age: 4.0,
+		};
+		let width = |years: f64| scale.position(years + 1.0) - scale.position(years);
+		assert!((width(0.0) - width(2.0)).abs() < 1e-9);
+		assert!((width(9.0) / width(19.0) - 2.0).abs() < 0.1);
+	}
+
+	#[test]
+	fn radial_labels_face_away_from_the_line() {
+		let track = Track {
+			layout: &LogLayout::Radial,
+			origin: (100.0, 100.0),
+			length: SWEEP,
+			radius: 50.0,
+		};
+		let (x, y) = track.point(TAU / 4.0, 10.0);
+		assert!((x - 160.0).abs() < 1e-9 && (y - 100.0).abs() < 1e-9);
+		// On the right of the circle, text outside starts at the line and text inside ends at it.
+		assert_eq!(track.text_anchor(TAU / 4.0, 10.0, 20.0, 80.0).1, "start");
+		assert_eq!(track.text_anchor(TAU / 4.0, -10.0, 20.0, 80.0).1, "end");
+		// At the bottom, text is centered below the line, clear of it by half its height.
+		let ((x, y), anchor) = track.text_anchor(PI, 10.0, 20.0, 80.0);
+		assert_eq!(anchor, "middle");
+		assert!((x - 100.0).abs() < 1e-9 && (y - 170.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn months_are_ticked_where_there_is_room() {
+		let life_info = LifeInfo {
+			birthday: Date::new(1990, 5, 5).unwrap(),
+			lifespan_years: 80,
+		};
+		let drawing_info = super::super::tests::drawing_info(false, false);
+		let render = |args: &[&str]| {
+			let style = <LogStyle as clap::Parser>::parse_from(args);
+			render_svg(&life_info, &drawing_info, &style)
+				.unwrap()
+				.to_string()
+		};
+		let classic = render(&["log"]);
+		assert!(classic.contains("fill-opacity=\"0.15\""));
+		assert!(classic.contains("stroke-width=\"1\""));
+		assert!(!render(&["log", "--scale", "linear"]).contains("stroke-width=\"1\""));
+	}
+
+	#[test]
+	fn labels_that_would_overlap_go_on_separate_rows() {
+		let spans = [
+			(0.0, 10.0),
+			(5.0, 15.0),
+			(11.0, 20.0),
+			(30.0, 40.0),
+			(8.0, 12.0),
+		];
+		assert_eq!(stack_labels(&spans, 1.0), [0, 1, 0, 0, 2]);
+	}
+}
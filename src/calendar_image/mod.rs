@@ -1,74 +1,920 @@
 #![allow(clippy::default_numeric_fallback)]
+use std::{
+	ffi::{OsStr, OsString},
+	fmt::Display,
+	fs::{self, File},
+	io::{self, BufWriter, StdoutLock, Write},
+	num::TryFromIntError,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use csscolorparser::Color;
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
 // This is due to a false positive
-use svg::{node::element::Rectangle, Document, Node};
+use svg::{
+	node::{
+		element::{Description, Image, Rectangle, Style, Title},
+		Text as TextNode,
+	},
+	Document, Node,
+};
+
+use death_calendar::death_day;
 
-use crate::{Drawing, DrawingInfo, DrawingInfoValidated, LifeInfo};
+use crate::{
+	color::{self, Gradient},
+	config::Config,
+	death_info, events, markers, phases,
+	theme::{self, Theme},
+	Drawing, DrawingInfo, DrawingInfoValidated, LifeInfo,
+};
 
+mod font;
 pub mod grid;
+pub mod hourglass;
+mod html;
 pub mod logarithmic;
+pub mod pattern;
 
 pub const AVERAGE_DAYS_IN_YEAR: f64 = 365.2425;
-pub const PHI: f64 = 1.618_033_988_749_895;
+pub const PHI: f64 = std::f64::consts::GOLDEN_RATIO;
 pub const WEEKS_IN_A_YEAR: u32 = 52;
 
-pub fn init_document(viewbox_width: f64, viewbox_height: f64, color_secondary: &str) -> Document {
+pub fn init_document(
+	viewbox_width: f64,
+	viewbox_height: f64,
+	drawing_info: &DrawingInfoValidated,
+	life_info: &LifeInfo,
+) -> anyhow::Result<Document> {
+	pixels(viewbox_width)?;
+	pixels(viewbox_height)?;
+	// Screen readers announce the image as one picture, using its title and description.
 	let mut document = Document::new()
 		.set("viewBox", (0_u8, 0_u8, viewbox_width, viewbox_height))
-		.set("style", format!("fill:{color_secondary}"));
+		.set("role", "img")
+		.set("aria-labelledby", "title desc");
+	document.append(Title::new("Death calendar").set("id", "title"));
+	document.append(
+		Description::new()
+			.set("id", "desc")
+			.add(TextNode::new(death_info::summary(
+				life_info.birthday,
+				life_info.lifespan_years,
+			)?)),
+	);
+
+	match drawing_info.background {
+		Backdrop::None => {},
+		Backdrop::Color(ref color) => {
+			let mut background = Rectangle::new()
+				.set("x", 0_u8)
+				.set("y", 0_u8)
+				.set("width", viewbox_width)
+				.set("height", viewbox_height)
+				.set("fill", color.to_hex_string());
+			if drawing_info.background_opacity < 1.0 {
+				background = background.set("fill-opacity", drawing_info.background_opacity);
+			}
+			document.append(background);
+		},
+		// The picture covers the whole image, cropping whatever doesn't fit.
+		Backdrop::Image(ref href) => {
+			let mut background = Image::new()
+				.set("x", 0_u8)
+				.set("y", 0_u8)
+				.set("width", viewbox_width)
+				.set("height", viewbox_height)
+				.set("preserveAspectRatio", "xMidYMid slice")
+				.set("href", href.as_str());
+			if drawing_info.background_opacity < 1.0 {
+				background = background.set("opacity", drawing_info.background_opacity);
+			}
+			document.append(background);
+		},
+	}
 
-	let background = Rectangle::new()
-		.set("x", 0_u8)
-		.set("y", 0_u8)
-		.set("width", viewbox_width)
-		.set("height", viewbox_height)
-		.set("fill", color_secondary);
-	document.append(background);
+	Ok(document)
+}
 
-	document
+/// Read a picture into a data URI, so it can go inside an image without a file next to it.
+fn data_uri(path: &Path) -> anyhow::Result<String> {
+	let extension = path
+		.extension()
+		.and_then(OsStr::to_str)
+		.map(str::to_ascii_lowercase);
+	let mime = match extension.as_deref() {
+		Some("png") => "image/png",
+		Some("jpg" | "jpeg") => "image/jpeg",
+		Some("gif") => "image/gif",
+		Some("webp") => "image/webp",
+		Some("svg") => "image/svg+xml",
+		_ => bail!("Background images have to be PNG, JPEG, GIF, WebP or SVG files"),
+	};
+	let data = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+	Ok(format!(
+		"data:{mime};base64,{}",
+		BASE64_STANDARD.encode(data)
+	))
 }
 
-fn linear_invert_color(c: &Color) -> Color {
-	Color::new(1.0 - c.r, 1.0 - c.g, 1.0 - c.b, c.a)
+pub fn str_to_charcount(s: &str) -> Result<u32, TryFromIntError> {
+	u32::try_from(s.chars().count())
 }
 
-pub fn draw_calendar(
-	drawing_type: Drawing,
-	drawing_info: DrawingInfo,
+pub fn num_to_charcount(num: u32) -> anyhow::Result<u32> {
+	Ok(str_to_charcount(&num.to_string())?)
+}
+
+/// The widest or tallest an image can be. It's well under `u32::MAX`, so positions inside the
+/// image can be added up without overflowing.
+const MAX_PIXELS: u32 = u32::MAX / 16;
+
+/// Round a size down to whole pixels, or fail if it's bigger than any image can be.
+pub fn pixels(size: f64) -> anyhow::Result<u32> {
+	if !(0.0..=f64::from(MAX_PIXELS)).contains(&size) {
+		bail!("The image would be too big to draw. Try a smaller scale factor or sizes.");
+	}
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	Ok(size as u32)
+}
+
+/// Gives each different set of colors a CSS class, so that shapes which look the same can share
+/// one rule in a `<style>` block instead of repeating their colors.
+#[derive(Default)]
+pub struct StyleClasses {
+	rules: Vec<String>,
+}
+
+impl StyleClasses {
+	/// The name of the class for a set of colors, adding a new one if it hasn't been seen yet.
+	pub fn class(&mut self, fill: &str, stroke: &str, stroke_width: impl Display) -> String {
+		let rule = format!("fill:{fill};stroke:{stroke};stroke-width:{stroke_width}");
+		let index = self
+			.rules
+			.iter()
+			.position(|existing| *existing == rule)
+			.unwrap_or_else(|| {
+				self.rules.push(rule);
+				self.rules.len() - 1
+			});
+		format!("s{index}")
+	}
+
+	pub fn to_style(&self) -> Style {
+		Style::new(
+			(0..)
+				.zip(&self.rules)
+				.map(|(index, rule)| format!(".s{index}{{{rule}}}"))
+				.collect::<String>(),
+		)
+	}
+}
+
+/// Somewhere the elements of an image can be put as they're drawn.
+pub trait Canvas {
+	fn draw<T: Into<Box<dyn Node>>>(&mut self, node: T) -> io::Result<()>;
+}
+
+impl Canvas for Document {
+	fn draw<T: Into<Box<dyn Node>>>(&mut self, node: T) -> io::Result<()> {
+		self.append(node);
+		Ok(())
+	}
+}
+
+/// Writes an image out an element at a time, instead of keeping all of it in memory.
+///
+/// What it writes is exactly what the same [`Document`] would have been written as. The only
+/// exception is a document that never gets any elements, which is closed with `</svg>` rather
+/// than `/>`.
+pub struct SvgStream<W: Write> {
+	out: W,
+	/// Leave out the line breaks between elements
+	minify: bool,
+	/// Whether the last thing written was text, which isn't put on its own line
+	last_bare: bool,
+}
+
+impl<W: Write> SvgStream<W> {
+	/// Start writing an image, beginning with the root element and anything already in it.
+	pub fn new(out: W, document: &Document, minify: bool) -> io::Result<Self> {
+		let text = document.to_string();
+		let (start, last_bare) = if let Some(start) = text.strip_suffix("\n</svg>") {
+			(start.to_owned(), false)
+		} else if let Some(start) = text.strip_suffix("</svg>") {
+			(start.to_owned(), true)
+		} else {
+			(format!("{}>", text.trim_end_matches("/>")), true)
+		};
+		let mut stream = Self {
+			out,
+			minify,
+			last_bare,
+		};
+		if minify {
+			stream.write_minified(&start)?;
+		} else {
+			stream.out.write_all(start.as_bytes())?;
+		}
+		Ok(stream)
+	}
+
+	// Line breaks only ever go between elements, since text inside them is written on one line.
+	fn write_minified(&mut self, text: &str) -> io::Result<()> {
+		self.out.write_all(text.replace(">\n<", "><").as_bytes())
+	}
+
+	/// Close the root element and hand back the writer.
+	pub fn finish(mut self) -> io::Result<W> {
+		if !self.last_bare && !self.minify {
+			self.out.write_all(b"\n")?;
+		}
+		self.out.write_all(b"</svg>")?;
+		Ok(self.out)
+	}
+}
+
+impl<W: Write> Canvas for SvgStream<W> {
+	fn draw<T: Into<Box<dyn Node>>>(&mut self, node: T) -> io::Result<()> {
+		let node: Box<dyn Node> = node.into();
+		self.last_bare = node.is_bare();
+		if self.minify {
+			self.write_minified(&node.to_string())
+		} else if self.last_bare {
+			write!(self.out, "{node}")
+		} else {
+			write!(self.out, "\n{node}")
+		}
+	}
+}
+
+#[derive(Debug, Clone, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+	Svg,
+	/// A web page that works offline, with the image, tooltips and some stats about your life
+	Html,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FillMode {
+	/// The primary color for the past and the secondary color for the future
+	Solid,
+	/// The past fades from `--color-birth` into the primary color by today, and the future fades
+	/// from the secondary color into `--color-death`
+	Gradient,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Background {
+	/// Nothing, so whatever the image is put on shows through
+	None,
+	/// The background color
+	Color,
+	/// The picture in `--background-image`
+	Image,
+}
+
+/// What's drawn behind a calendar.
+pub enum Backdrop {
+	None,
+	Color(Color),
+	/// A picture, as a data URI
+	Image(String),
+}
+
+/// Where a finished image goes.
+enum Output {
+	Stdout(StdoutLock<'static>),
+	File(BufWriter<File>, Replacement),
+	/// A file compressed with gzip, for `.svgz` files
+	Gzip(GzEncoder<BufWriter<File>>, Replacement),
+}
+
+/// A file written next to the one it's meant to be, so that a half-written image or page never
+/// takes the place of one that's already there. It's thrown away unless it's put in place.
+pub struct Replacement {
+	temporary: PathBuf,
+	target: PathBuf,
+	done: bool,
+}
+
+impl Replacement {
+	pub fn create(target: &Path) -> anyhow::Result<(Self, File)> {
+		let mut name = OsString::from(".");
+		name.push(target.file_name().unwrap_or_else(|| OsStr::new("image")));
+		name.push(format!(".{}.tmp", std::process::id()));
+		let temporary = target.with_file_name(name);
+		let file = File::create(&temporary)
+			.with_context(|| format!("Could not write {}", target.display()))?;
+		let replacement = Self {
+			temporary,
+			target: target.to_path_buf(),
+			done: false,
+		};
+		Ok((replacement, file))
+	}
+
+	pub fn put_in_place(mut self) -> io::Result<()> {
+		fs::rename(&self.temporary, &self.target)?;
+		self.done = true;
+		Ok(())
+	}
+}
+
+impl Drop for Replacement {
+	fn drop(&mut self) {
+		if !self.done {
+			// It might never have been written, and there's nothing else to do if it can't go.
+			let _ = fs::remove_file(&self.temporary);
+		}
+	}
+}
+
+impl Output {
+	fn open(path: Option<&Path>) -> anyhow::Result<Self> {
+		let Some(path) = path else {
+			return Ok(Self::Stdout(io::stdout().lock()));
+		};
+		let (replacement, file) = Replacement::create(path)?;
+		let file = BufWriter::new(file);
+		let gzip = path
+			.extension()
+			.is_some_and(|ext| ext.eq_ignore_ascii_case("svgz"));
+		Ok(if gzip {
+			Self::Gzip(GzEncoder::new(file, Compression::best()), replacement)
+		} else {
+			Self::File(file, replacement)
+		})
+	}
+
+	fn finish(self) -> io::Result<()> {
+		match self {
+			Self::Stdout(mut stdout) => {
+				writeln!(stdout)?;
+				stdout.flush()
+			},
+			Self::File(file, replacement) => {
+				file.into_inner().map_err(io::IntoInnerError::into_error)?;
+				replacement.put_in_place()
+			},
+			Self::Gzip(gzip, replacement) => {
+				gzip.finish()?
+					.into_inner()
+					.map_err(io::IntoInnerError::into_error)?;
+				replacement.put_in_place()
+			},
+		}
+	}
+}
+
+impl Write for Output {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match *self {
+			Self::Stdout(ref mut stdout) => stdout.write(buf),
+			Self::File(ref mut file, _) => file.write(buf),
+			Self::Gzip(ref mut gzip, _) => gzip.write(buf),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match *self {
+			Self::Stdout(ref mut stdout) => stdout.flush(),
+			Self::File(ref mut file, _) => file.flush(),
+			Self::Gzip(ref mut gzip, _) => gzip.flush(),
+		}
+	}
+}
+
+/// Whether any text gets drawn, which then has to stand out against the background.
+fn has_text(drawing_type: &Drawing, drawing_info: &DrawingInfoValidated) -> bool {
+	match *drawing_type {
+		Drawing::Grid { ref labels, .. } => {
+			labels.year_label_interval.is_some()
+				|| labels.cell_label_interval.is_some()
+				|| !drawing_info.phases.is_empty()
+				|| !drawing_info.events.is_empty()
+		},
+		Drawing::Logarithmic { .. } => true,
+		Drawing::Hourglass { .. } => false,
+	}
+}
+
+/// Warnings about colors that are hard to tell apart, or an error when `strict` is set. Text is
+/// only checked when there is some.
+fn check_colors(
+	drawing_info: &DrawingInfoValidated,
+	theme: &Theme,
+	strict: bool,
+	text: bool,
+) -> anyhow::Result<Vec<String>> {
+	let mut problems = Vec::new();
+	let mut pairs = vec![(
+		"primary and secondary colors",
+		&drawing_info.color_primary,
+		&drawing_info.color_secondary,
+		color::MIN_SHAPE_CONTRAST,
+	)];
+	if text {
+		pairs.push((
+			"text and background colors",
+			&drawing_info.color_text,
+			&drawing_info.color_background,
+			color::MIN_TEXT_CONTRAST,
+		));
+	}
+	for (what, first, second, min_contrast) in pairs {
+		let contrast = color::contrast(first, second);
+		if contrast < min_contrast {
+			problems.push(format!(
+				"The {what} have a contrast of {contrast:.1}:1, under the {min_contrast}:1 WCAG \
+				 asks for"
+			));
+		}
+	}
+	problems.extend(theme.deuteranopia_problems());
+	if strict && !problems.is_empty() {
+		bail!("{}", problems.join("\n"));
+	}
+	Ok(problems)
+}
+
+/// Fill in the colors that were left out, and read the events, phases and markers. Comes with
+/// warnings about the colors.
+fn validate(
+	drawing_type: &Drawing,
+	drawing_info: &DrawingInfo,
 	life_info: &LifeInfo,
-) -> anyhow::Result<()> {
-	let drawing_info_validated = DrawingInfoValidated {
+	config: &Config,
+) -> anyhow::Result<(DrawingInfoValidated, Vec<String>)> {
+	let color_secondary = {
+		if let Some(ref color) = drawing_info.color_secondary {
+			color.clone()
+		} else {
+			color::opposite(&drawing_info.color_primary)
+		}
+	};
+	let or_primary = |color: &Option<Color>| {
+		color
+			.clone()
+			.unwrap_or_else(|| drawing_info.color_primary.clone())
+	};
+	if !(0.0..=1.0).contains(&drawing_info.background_opacity) {
+		bail!("The background opacity has to be between 0 and 1");
+	}
+	let color_background = drawing_info
+		.color_background
+		.clone()
+		.unwrap_or_else(|| color_secondary.clone());
+	let background = match drawing_info.background {
+		Background::None => Backdrop::None,
+		Background::Color => Backdrop::Color(color_background.clone()),
+		Background::Image => Backdrop::Image(data_uri(
+			drawing_info
+				.background_image
+				.as_deref()
+				.context("A background image needs a picture, given with `--background-image`")?,
+		)?),
+	};
+	let theme = drawing_info
+		.theme
+		.as_deref()
+		.map(theme::load)
+		.transpose()?
+		.unwrap_or_default();
+	let validated = DrawingInfoValidated {
 		scale_factor: drawing_info.scale_factor,
 		color_primary: drawing_info.color_primary.clone(),
-		color_secondary: {
-			if let Some(color) = drawing_info.color_secondary {
-				color
-			} else {
-				linear_invert_color(&drawing_info.color_primary)
-			}
+		phases: phases::resolve(
+			&config.phases,
+			life_info.birthday,
+			death_day(life_info.birthday, life_info.lifespan_years.try_into()?),
+			&color_secondary,
+			&theme.phases,
+		)?,
+		markers: markers::resolve(
+			&config.markers,
+			life_info.birthday,
+			&or_primary(&drawing_info.color_accent),
+		)?,
+		gradient: matches!(drawing_info.fill_mode, FillMode::Gradient).then(|| Gradient {
+			birth: drawing_info.color_birth.clone().unwrap_or_else(|| {
+				drawing_info
+					.color_primary
+					.interpolate_oklab(&color_secondary, 0.5)
+			}),
+			past: drawing_info.color_primary.clone(),
+			future: color_secondary.clone(),
+			death: drawing_info.color_death.clone().unwrap_or_else(|| {
+				color_secondary.interpolate_oklab(&drawing_info.color_primary, 0.5)
+			}),
+		}),
+		color_background,
+		background,
+		background_opacity: drawing_info.background_opacity,
+		color_secondary,
+		color_present: or_primary(&drawing_info.color_present),
+		color_stroke: or_primary(&drawing_info.color_stroke),
+		color_text: or_primary(&drawing_info.color_text),
+		color_accent: or_primary(&drawing_info.color_accent),
+		events: match drawing_info.events {
+			Some(ref path) => events::load(path)?,
+			None => Vec::new(),
 		},
+		past_pattern: drawing_info.past_pattern,
+		present_pattern: drawing_info.present_pattern,
+		future_pattern: drawing_info.future_pattern,
+		event_pattern: drawing_info.event_pattern,
+		compact: drawing_info.compact,
+		interactive: matches!(drawing_info.format, Format::Html),
 	};
+	let warnings = check_colors(
+		&validated,
+		&theme,
+		drawing_info.strict,
+		has_text(drawing_type, &validated),
+	)?;
+	Ok((validated, warnings))
+}
+
+/// Draw a calendar and save it, handing back any warnings about how it looks.
+pub fn draw_calendar(
+	drawing_type: Drawing,
+	drawing_info: DrawingInfo,
+	life_info: &LifeInfo,
+	config: &Config,
+) -> anyhow::Result<Vec<String>> {
+	let (drawing_info_validated, warnings) =
+		validate(&drawing_type, &drawing_info, life_info, config)?;
+	let mut out = Output::open(drawing_info.output.as_deref())?;
+	write_calendar(
+		&mut out,
+		&drawing_type,
+		&drawing_info,
+		&drawing_info_validated,
+		life_info,
+		&warnings,
+	)?;
+	out.finish()?;
+	Ok(warnings)
+}
 
-	let document: Document = match drawing_type {
+/// Draw a calendar in memory instead of saving it anywhere, along with any warnings about how it
+/// looks.
+pub fn render_calendar(
+	drawing_type: &Drawing,
+	drawing_info: &DrawingInfo,
+	life_info: &LifeInfo,
+	config: &Config,
+) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+	let (drawing_info_validated, warnings) =
+		validate(drawing_type, drawing_info, life_info, config)?;
+	let mut out = Vec::new();
+	write_calendar(
+		&mut out,
+		drawing_type,
+		drawing_info,
+		&drawing_info_validated,
+		life_info,
+		&warnings,
+	)?;
+	Ok((out, warnings))
+}
+
+fn write_calendar<W: Write>(
+	out: &mut W,
+	drawing_type: &Drawing,
+	drawing_info: &DrawingInfo,
+	drawing_info_validated: &DrawingInfoValidated,
+	life_info: &LifeInfo,
+	warnings: &[String],
+) -> anyhow::Result<()> {
+	let minify = drawing_info.minify;
+	let format = &drawing_info.format;
+	let document: Document = match *drawing_type {
+		// Grids can have so many shapes that they're written out as they're drawn.
 		Drawing::Grid {
-			grid_ratios,
-			week_shape,
-		} => grid::render_svg(
-			life_info,
-			&drawing_info_validated,
-			&grid_ratios,
-			&week_shape,
-		)?,
-		Drawing::Logarithmic { width_height_ratio } => {
-			logarithmic::render_svg(life_info, &drawing_info_validated, width_height_ratio)?
+			ref grid_ratios,
+			ref style,
+			ref layout,
+			ref labels,
+		} => {
+			return write_image(out, format, life_info, warnings, |out| {
+				grid::render(
+					life_info,
+					drawing_info_validated,
+					grid_ratios,
+					style,
+					layout,
+					labels,
+					|document| Ok(SvgStream::new(out, &document, minify)?),
+				)?
+				.finish()?;
+				Ok(())
+			});
+		},
+		Drawing::Logarithmic { ref style } => {
+			logarithmic::render_svg(life_info, drawing_info_validated, style)?
+		},
+		Drawing::Hourglass { ref style } => {
+			hourglass::render_svg(life_info, drawing_info_validated, style)?
 		},
 	};
+	write_image(out, format, life_info, warnings, |out| {
+		SvgStream::new(out, &document, minify)?.finish()?;
+		Ok(())
+	})
+}
 
+/// Write an image with `draw`, wrapped in a web page with `warnings` if that's the format it should
+/// be in.
+fn write_image<W: Write>(
+	out: &mut W,
+	format: &Format,
+	life_info: &LifeInfo,
+	warnings: &[String],
+	draw: impl FnOnce(&mut W) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+	match *format {
+		Format::Svg => draw(out),
+		Format::Html => {
+			html::write_start(out, life_info, warnings)?;
+			draw(out)?;
+			html::write_end(out)
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Instant;
+
+	use clap::Parser;
+
+	use super::{pattern::Pattern, *};
+	use crate::{GridLabels, GridLayout, GridRatios, GridStyle};
+
+	pub(super) fn drawing_info(compact: bool, interactive: bool) -> DrawingInfoValidated {
+		DrawingInfoValidated {
+			scale_factor: 1,
+			color_primary: Color::new(0.0, 0.0, 0.0, 1.0),
+			color_secondary: Color::new(1.0, 1.0, 1.0, 1.0),
+			color_present: Color::new(1.0, 0.0, 0.0, 1.0),
+			color_background: Color::new(1.0, 1.0, 1.0, 1.0),
+			background: Backdrop::Color(Color::new(1.0, 1.0, 1.0, 1.0)),
+			background_opacity: 1.0,
+			color_stroke: Color::new(0.0, 0.0, 0.0, 1.0),
+			color_text: Color::new(0.0, 0.0, 0.0, 1.0),
+			color_accent: Color::new(0.0, 0.0, 0.0, 1.0),
+			events: Vec::new(),
+			phases: Vec::new(),
+			markers: Vec::new(),
+			gradient: None,
+			past_pattern: Pattern::Solid,
+			present_pattern: Pattern::Solid,
+			future_pattern: Pattern::Solid,
+			event_pattern: Pattern::Solid,
+			compact,
+			interactive,
+		}
+	}
+
+	fn life_info(lifespan_years: &str) -> LifeInfo {
+		LifeInfo {
+			birthday: gregorian::Date::new(1990, 5, 5).unwrap(),
+			lifespan_years: lifespan_years.parse().unwrap(),
+		}
+	}
+
+	fn render_grid<C: Canvas>(
+		drawing_info: &DrawingInfoValidated,
+		lifespan_years: &str,
+		labels: &[&str],
+		begin: impl FnOnce(Document) -> anyhow::Result<C>,
+	) -> C {
+		grid::render(
+			&life_info(lifespan_years),
+			drawing_info,
+			&GridRatios::parse_from(["grid"]),
+			&GridStyle::parse_from(["grid", "--present-progress"]),
+			&GridLayout::parse_from(["grid"]),
+			&GridLabels::parse_from(["grid"].iter().chain(labels)),
+			begin,
+		)
+		.unwrap()
+	}
+
+	fn stream<W: Write>(out: W, document: &Document, minify: bool) -> W {
+		SvgStream::new(out, document, minify)
+			.unwrap()
+			.finish()
+			.unwrap()
+	}
+
+	#[test]
+	fn compact_minified_and_gzipped_grids_are_smaller() {
+		let full = render_grid(&drawing_info(false, false), "100", &[], Ok);
+		let compact = render_grid(&drawing_info(true, false), "100", &[], Ok);
+		let full_size = stream(Vec::new(), &full, false).len();
+		let compact_size = stream(Vec::new(), &compact, false).len();
+		let minified_size = stream(Vec::new(), &compact, true).len();
+		let gzip = GzEncoder::new(Vec::new(), Compression::best());
+		let gzipped_size = stream(gzip, &compact, true).finish().unwrap().len();
+		let report = format!(
+			"full: {full_size}, compact: {compact_size}, minified: {minified_size}, gzipped: \
+			 {gzipped_size} bytes"
+		);
+		assert!(compact_size * 5 < full_size * 3, "{report}");
+		assert!(minified_size < compact_size, "{report}");
+		assert!(gzipped_size * 10 < minified_size, "{report}");
+	}
+
+	#[test]
+	fn streaming_writes_the_same_as_the_document() {
+		const LABELS: &[&str] = &["--year-label-interval", "10", "--cell-tooltips"];
+		// Written out from a `Document` before grids were streamed, with the compact style sheet
+		// and definitions moved after the cells. Only the description changes from day to day.
+		let summary = death_info::summary(life_info("3").birthday, 3).unwrap();
+		for (compact, expected) in [
+			(false, include_str!("../../tests/fixtures/grid.svg")),
+			(true, include_str!("../../tests/fixtures/grid-compact.svg")),
+		] {
+			let expected = expected.replace("{summary}", &summary);
+			let mut drawing_info = drawing_info(compact, false);
+			drawing_info.phases.push(phases::Phase {
+				name: "Toddler".to_string(),
+				start: gregorian::Date::new(1991, 5, 5).unwrap(),
+				end: gregorian::Date::new(1992, 5, 5).unwrap(),
+				color_past: Color::new(1.0, 0.0, 0.0, 1.0),
+				color_future: Color::new(0.0, 0.0, 1.0, 1.0),
+			});
+			let document = render_grid(&drawing_info, "3", LABELS, Ok);
+			assert_eq!(document.to_string(), expected);
+			for minify in [false, true] {
+				let streamed = render_grid(&drawing_info, "3", LABELS, |document| {
+					Ok(SvgStream::new(Vec::new(), &document, minify)?)
+				})
+				.finish()
+				.unwrap();
+				let streamed = String::from_utf8(streamed).unwrap();
+				if minify {
+					assert_eq!(streamed, expected.replace(">\n<", "><"));
+				} else {
+					assert_eq!(streamed, expected);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn failed_images_leave_the_old_file_alone() {
+		let path = std::env::temp_dir().join("death-calendar-keep.svg");
+		let draw = |args: &[&str]| {
+			let cli = crate::Cli::parse_from(
+				[
+					"death-calendar",
+					"1990-01-01",
+					"img",
+					"-o",
+					path.to_str().unwrap(),
+				]
+				.iter()
+				.chain(args),
+			);
+			let crate::Commands::Image {
+				drawing_type,
+				drawing_info,
+			} = cli.command
+			else {
+				panic!("Expected an image");
+			};
+			draw_calendar(
+				drawing_type,
+				drawing_info,
+				&life_info("80"),
+				&Config::default(),
+			)
+		};
+		fs::write(&path, "old").unwrap();
+		assert!(draw(&["log", "--width-height-ratio", "0.5"]).is_err());
+		assert!(draw(&["grid", "--align", "calendar", "--wrap", "5"]).is_err());
+		assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+		draw(&["log"]).unwrap();
+		assert!(fs::read_to_string(&path).unwrap().starts_with("<svg"));
+		let leftovers = fs::read_dir(std::env::temp_dir())
+			.unwrap()
+			.filter(|entry| {
+				let name = entry.as_ref().unwrap().file_name();
+				name.to_string_lossy()
+					.starts_with(".death-calendar-keep.svg.")
+			})
+			.count();
+		assert_eq!(leftovers, 0);
+	}
+
+	#[test]
+	fn text_contrast_is_only_checked_when_there_is_text() {
+		let mut info = drawing_info(false, false);
+		info.color_text = Color::new(0.9, 0.9, 0.9, 1.0);
+		let theme = Theme::default();
+		assert_eq!(check_colors(&info, &theme, false, false).unwrap().len(), 0);
+		let warnings = check_colors(&info, &theme, false, true).unwrap();
+		assert!(warnings[0].starts_with("The text and background colors"));
+		assert!(check_colors(&info, &theme, true, true).is_err());
+	}
+
+	#[test]
+	fn images_describe_themselves() {
+		let document =
+			render_grid(&drawing_info(false, false), "1", &["--cell-tooltips"], Ok).to_string();
+		assert!(document.contains(r#"aria-labelledby="title desc" role="img""#));
+		assert!(document.contains(r#"<title id="title">Death calendar</title>"#));
+		assert!(document.contains(r#"<desc id="desc">Born on 1990-05-05 and now aged"#));
+		assert!(document.contains("<title>Week 1: 1990-05-05 to 1990-05-12, age 0</title>"));
+		assert!(document.contains("<title>Week 52: 1991-04-28 to 1991-05-04, age 0</title>"));
+	}
+
+	#[test]
+	fn backgrounds_can_be_left_out_or_be_pictures() {
+		let mut info = drawing_info(false, false);
+		info.background = Backdrop::None;
+		let document = render_grid(&info, "1", &[], Ok).to_string();
+		assert!(!document.contains(r#"x="0" y="0""#));
+		assert!(!document.contains("style="));
+
+		let path = std::env::temp_dir().join("death-calendar-background.svg");
+		fs::write(&path, "<svg/>").unwrap();
+		info.background = Backdrop::Image(data_uri(&path).unwrap());
+		info.background_opacity = 0.5;
+		let document = render_grid(&info, "1", &[], Ok).to_string();
+		assert!(document.contains(r#"href="data:image/svg+xml;base64,PHN2Zy8+""#));
+		assert!(document.contains(r#"opacity="0.5""#));
+		assert!(data_uri(Path::new("background.bmp")).is_err());
+	}
+
+	#[test]
+	fn web_pages_hold_the_image_and_stats() {
+		let mut page = Vec::new();
+		let warnings = ["The text & background colors look alike".to_owned()];
+		write_image(
+			&mut page,
+			&Format::Html,
+			&life_info("1"),
+			&warnings,
+			|out| {
+				render_grid(&drawing_info(false, true), "1", &[], |document| {
+					Ok(SvgStream::new(out, &document, false)?)
+				})
+				.finish()?;
+				Ok(())
+			},
+		)
+		.unwrap();
+		let page = String::from_utf8(page).unwrap();
+		assert!(page.starts_with("<!DOCTYPE html>"));
+		assert!(page.contains(
+			"<p class=\"warning\">Warning: The text &amp; background colors look alike</p>\n<pre>Your \
+			 birthday is 1990-05-05."
+		));
+		assert!(
+			page.contains(r#"<rect data-age="0" data-end="1990-05-12" data-start="1990-05-05""#)
+		);
+		assert!(page.ends_with("</html>"));
+	}
+
+	#[test]
+	#[ignore = "benchmark, run it with `cargo test --release -- --ignored --nocapture`"]
 	#[allow(clippy::print_stdout)]
-	if let Some(filename) = drawing_info.output {
-		svg::save(filename, &document)?;
-	} else {
-		println!("{document}");
+	fn streaming_compared_to_building_a_document() {
+		for lifespan_years in ["100", "1000"] {
+			let start = Instant::now();
+			let document = render_grid(&drawing_info(false, false), lifespan_years, &[], Ok);
+			let built = stream(Vec::new(), &document, false);
+			let building_time = start.elapsed();
+			drop(document);
+
+			let start = Instant::now();
+			let streamed = render_grid(
+				&drawing_info(false, false),
+				lifespan_years,
+				&[],
+				|document| Ok(SvgStream::new(Vec::new(), &document, false)?),
+			)
+			.finish()
+			.unwrap();
+			let streaming_time = start.elapsed();
+
+			assert_eq!(built, streamed);
+			println!(
+				"{lifespan_years} years, {} bytes: document {building_time:?}, streamed \
+				 {streaming_time:?}",
+				built.len()
+			);
+		}
 	}
-	Ok(())
 }
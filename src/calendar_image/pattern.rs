@@ -0,0 +1,149 @@
+use csscolorparser::Color;
+use serde::{Deserialize, Serialize};
+use svg::node::element::{Circle, Definitions, Pattern as PatternElement, Rectangle};
+
+use crate::color;
+
+/// How the shapes of a part of a life are filled in, so they can be told apart without color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pattern {
+	/// All one color
+	#[default]
+	Solid,
+	/// Diagonal lines
+	Hatching,
+	/// A grid of dots
+	Dots,
+	/// Diagonal lines both ways
+	CrossHatch,
+}
+
+/// The patterns used in an image, each made into a `<pattern>` the first time it's asked for.
+///
+/// Patterns are drawn over a ground color, in an ink color that stands out against it.
+pub struct Patterns {
+	/// How far apart the lines and dots are
+	size: f64,
+	/// Colors that patterns can be drawn in when they don't have their own
+	inks: [Color; 2],
+	defined: Vec<(Pattern, String, String)>,
+}
+
+impl Patterns {
+	pub fn new(size: f64, stroke: &Color, background: &Color) -> Self {
+		Self {
+			size,
+			inks: [stroke.clone(), background.clone()],
+			defined: Vec::new(),
+		}
+	}
+
+	/// The fill for a pattern over `ground`, which is just `ground` when the pattern is solid.
+	pub fn fill(&mut self, pattern: Pattern, ground: &Color, ink: &Color) -> String {
+		if pattern == Pattern::Solid {
+			return ground.to_hex_string();
+		}
+		let key = (pattern, ground.to_hex_string(), ink.to_hex_string());
+		let index = self
+			.defined
+			.iter()
+			.position(|existing| *existing == key)
+			.unwrap_or_else(|| {
+				self.defined.push(key);
+				self.defined.len() - 1
+			});
+		format!("url(#pattern-{index})")
+	}
+
+	/// The fill for a pattern over `ground`, drawn in the stroke or background color, whichever
+	/// has more contrast with it.
+	pub fn contrasting_fill(&mut self, pattern: Pattern, ground: &Color) -> String {
+		let [ref stroke, ref background] = self.inks;
+		let ink = if color::contrast(ground, stroke) >= color::contrast(ground, background) {
+			stroke.clone()
+		} else {
+			background.clone()
+		};
+		self.fill(pattern, ground, &ink)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.defined.is_empty()
+	}
+
+	/// Every pattern that's been asked for. Lines run diagonally, and line up from one shape to
+	/// the next since they're all measured from the corner of the image.
+	pub fn to_definitions(&self) -> Definitions {
+		let size = self.size;
+		let strip = |ink: &str| {
+			Rectangle::new()
+				.set("width", size / 3.0)
+				.set("height", size)
+				.set("fill", ink)
+		};
+		(0..).zip(&self.defined).fold(
+			Definitions::new(),
+			|definitions, (index, (pattern, ground, ink))| {
+				let element = PatternElement::new()
+					.set("id", format!("pattern-{index}"))
+					.set("patternUnits", "userSpaceOnUse")
+					.set("width", size)
+					.set("height", size)
+					.add(
+						Rectangle::new()
+							.set("width", size)
+							.set("height", size)
+							.set("fill", ground.as_str()),
+					);
+				definitions.add(match *pattern {
+					Pattern::Solid => element,
+					Pattern::Hatching => element
+						.set("patternTransform", "rotate(45)")
+						.add(strip(ink)),
+					Pattern::CrossHatch => element
+						.set("patternTransform", "rotate(45)")
+						.add(strip(ink))
+						.add(strip(ink).set("width", size).set("height", size / 3.0)),
+					Pattern::Dots => element.add(
+						Circle::new()
+							.set("cx", size / 2.0)
+							.set("cy", size / 2.0)
+							.set("r", size / 4.0)
+							.set("fill", ink.as_str()),
+					),
+				})
+			},
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn patterns_are_defined_once_and_drawn_in_a_contrasting_ink() {
+		let black = Color::new(0.0, 0.0, 0.0, 1.0);
+		let white = Color::new(1.0, 1.0, 1.0, 1.0);
+		let mut patterns = Patterns::new(6.0, &black, &white);
+		assert_eq!(patterns.contrasting_fill(Pattern::Solid, &black), "#000000");
+		assert!(patterns.is_empty());
+		assert_eq!(
+			patterns.contrasting_fill(Pattern::Hatching, &black),
+			"url(#pattern-0)"
+		);
+		assert_eq!(
+			patterns.contrasting_fill(Pattern::Dots, &white),
+			"url(#pattern-1)"
+		);
+		assert_eq!(
+			patterns.contrasting_fill(Pattern::Hatching, &black),
+			"url(#pattern-0)"
+		);
+		let definitions = patterns.to_definitions().to_string();
+		assert!(definitions.contains(r##"fill="#000000" height="6" width="6""##));
+		assert!(definitions.contains(r##"fill="#ffffff" height="6" width="2""##));
+		assert!(definitions.contains(r##"<circle cx="3" cy="3" fill="#000000" r="1.5"/>"##));
+	}
+}
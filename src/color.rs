@@ -0,0 +1,140 @@
+use csscolorparser::Color;
+
+/// Lightness in OKLab where a color stands out as much against black as against white.
+const MIDDLE_LIGHTNESS: f64 = 0.57;
+
+/// Contrast ratio that shapes and lines need against what's next to them, from WCAG 2.1.
+pub const MIN_SHAPE_CONTRAST: f64 = 3.0;
+
+/// Contrast ratio that text needs against its background, from WCAG 2.1.
+pub const MIN_TEXT_CONTRAST: f64 = 4.5;
+
+/// How far apart two colors need to be in OKLab to be told apart at a glance.
+pub const MIN_DISTANCE: f64 = 0.06;
+
+/// A color that stands out against `color`, with the opposite lightness and hue.
+///
+/// Lightness is flipped around the middle instead of inverting each channel, which would leave
+/// mid-grey as mid-grey. Colors lighter than the middle get a dark opposite and the rest get a
+/// light one, close to the far end so that black and white swap.
+pub fn opposite(color: &Color) -> Color {
+	let (lightness, a, b, alpha) = color.to_oklaba();
+	let lightness = if lightness < MIDDLE_LIGHTNESS {
+		lightness.mul_add(-0.1, 1.0)
+	} else {
+		(1.0 - lightness) * 0.1
+	};
+	Color::from_oklaba(lightness, -a / 2.0, -b / 2.0, alpha).clamp()
+}
+
+/// How bright a color looks, from 0 for black to 1 for white, as WCAG measures it.
+fn relative_luminance(color: &Color) -> f64 {
+	let (r, g, b, _) = color.to_linear_rgba();
+	0.0722f64.mul_add(b, 0.2126f64.mul_add(r, 0.7152 * g))
+}
+
+/// The WCAG contrast ratio between two colors, from 1 for the same color to 21 for black on
+/// white.
+pub fn contrast(first: &Color, second: &Color) -> f64 {
+	let (first, second) = (relative_luminance(first), relative_luminance(second));
+	(first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+/// How far apart two colors look, as the distance between them in OKLab.
+pub fn distance(first: &Color, second: &Color) -> f64 {
+	let (l1, a1, b1, _) = first.to_oklaba();
+	let (l2, a2, b2, _) = second.to_oklaba();
+	(l1 - l2).hypot(a1 - a2).hypot(b1 - b2)
+}
+
+/// How a color looks to someone with deuteranopia, the most common kind of color blindness.
+///
+/// Uses the simulation from Machado, Oliveira and Fernandes (2009) at full severity.
+pub fn deuteranopia(color: &Color) -> Color {
+	let (r, g, b, alpha) = color.to_linear_rgba();
+	let mix = |[x, y, z]: [f64; 3]| z.mul_add(b, x.mul_add(r, y * g));
+	Color::from_linear_rgba(
+		mix([0.367_322, 0.860_646, -0.227_968]),
+		mix([0.280_085, 0.672_501, 0.047_413]),
+		mix([-0.011_820, 0.042_940, 0.968_881]),
+		alpha,
+	)
+	.clamp()
+}
+
+/// Colors that change across a lifespan, for `--fill-mode gradient`.
+#[derive(Debug, Clone)]
+pub struct Gradient {
+	/// The color on the day you were born
+	pub birth: Color,
+	/// The color of the time lived just before today
+	pub past: Color,
+	/// The color of the time just after today
+	pub future: Color,
+	/// The color on the expected day of death
+	pub death: Color,
+}
+
+impl Gradient {
+	/// The color of time already lived, `share` of the way from birth to today.
+	pub fn past_at(&self, share: f64) -> Color {
+		self.birth
+			.interpolate_oklab(&self.past, share.clamp(0.0, 1.0))
+	}
+
+	/// The color of time still to come, `share` of the way from today to the day of death.
+	pub fn future_at(&self, share: f64) -> Color {
+		self.future
+			.interpolate_oklab(&self.death, share.clamp(0.0, 1.0))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn opposites_stand_out() {
+		let black = Color::new(0.0, 0.0, 0.0, 1.0);
+		assert_eq!(opposite(&black).to_hex_string(), "#ffffff");
+		assert_eq!(
+			opposite(&Color::new(1.0, 1.0, 1.0, 1.0)).to_hex_string(),
+			"#000000"
+		);
+		for hex in [
+			"#808080", "#64727d", "#ff0000", "#2d3436", "#88c0d0", "#777777",
+		] {
+			let color = csscolorparser::parse(hex).unwrap();
+			assert!(
+				contrast(&color, &opposite(&color)) >= MIN_SHAPE_CONTRAST,
+				"{hex}"
+			);
+		}
+	}
+
+	#[test]
+	fn red_and_green_look_alike_with_deuteranopia() {
+		let red = csscolorparser::parse("#d62728").unwrap();
+		let green = csscolorparser::parse("#2ca02c").unwrap();
+		assert!(distance(&red, &green) > MIN_DISTANCE);
+		assert!(distance(&deuteranopia(&red), &deuteranopia(&green)) < MIN_DISTANCE);
+	}
+
+	#[test]
+	fn gradients_meet_at_today() {
+		let color = |hex| csscolorparser::parse(hex).unwrap();
+		let gradient = Gradient {
+			birth: color("orange"),
+			past: color("navy"),
+			future: color("white"),
+			death: color("#dddddd"),
+		};
+		assert_eq!(gradient.past_at(-1.0).to_hex_string(), "#ffa500");
+		assert_eq!(gradient.past_at(1.0).to_hex_string(), "#000080");
+		assert_eq!(gradient.future_at(0.0).to_hex_string(), "#ffffff");
+		assert_eq!(gradient.future_at(2.0).to_hex_string(), "#dddddd");
+		let middle = gradient.past_at(0.5);
+		assert!(distance(&middle, &gradient.birth) > MIN_DISTANCE);
+		assert!(distance(&middle, &gradient.past) > MIN_DISTANCE);
+	}
+}
@@ -0,0 +1,152 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory};
+use csscolorparser::Color;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
+
+use crate::{markers::MarkerEntry, phases::PhaseEntry};
+
+/// Settings read from the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Named stretches of a life, like childhood or retirement
+	pub phases: Vec<PhaseEntry>,
+	/// Days to point out on logarithmic calendars, like retirement
+	pub markers: Vec<MarkerEntry>,
+	/// Options for every kind of image, like `color_primary = "navy"`
+	pub image: toml::Table,
+	/// Options for grid calendars
+	pub grid: toml::Table,
+	/// Options for logarithmic calendars
+	pub log: toml::Table,
+	/// Options for hourglasses
+	pub hourglass: toml::Table,
+}
+
+/// Reads a color the way CSS does, and writes it as a hex code so it can be read back again.
+pub struct CssColor;
+
+impl SerializeAs<Color> for CssColor {
+	fn serialize_as<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+		color.to_hex_string().serialize(serializer)
+	}
+}
+
+impl<'de> DeserializeAs<'de, Color> for CssColor {
+	fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+		let color = String::deserialize(deserializer)?;
+		csscolorparser::parse(&color).map_err(serde::de::Error::custom)
+	}
+}
+
+/// Make sure every option in a table of the config file belongs to one of `commands`.
+pub fn check_keys(section: &str, table: &toml::Table, commands: &[clap::Command]) -> Result<()> {
+	for key in table.keys() {
+		let known = commands
+			.iter()
+			.flat_map(clap::Command::get_arguments)
+			.any(|arg| arg.get_id() == key.as_str());
+		if !known {
+			bail!("There's no option called `{key}` for `[{section}]` in the config file");
+		}
+	}
+	Ok(())
+}
+
+/// Fill in the options that weren't given on the command line from a table of the config file.
+///
+/// Options given on the command line always win. Anything in the table that isn't one of
+/// `options`' fields is left for other options to use.
+pub fn apply<T: CommandFactory + Serialize + DeserializeOwned>(
+	options: T,
+	table: &toml::Table,
+	matches: &ArgMatches,
+) -> Result<T> {
+	if table.is_empty() {
+		return Ok(options);
+	}
+	let mut fields = toml::Table::try_from(&options)?;
+	for arg in T::command().get_arguments() {
+		let id = arg.get_id().as_str();
+		let from_command_line = matches.value_source(id) == Some(ValueSource::CommandLine);
+		if let (Some(value), false) = (table.get(id), from_command_line) {
+			fields.insert(id.to_owned(), value.clone());
+		}
+	}
+	Ok(fields.try_into()?)
+}
+
+/// TOML has its own date type, but dates are read the same way from the command line and from
+/// CSV files, which only have strings. So turn all of the dates into strings first.
+fn dates_to_strings(value: toml::Value) -> toml::Value {
+	match value {
+		toml::Value::Datetime(date) => toml::Value::String(date.to_string()),
+		toml::Value::Array(values) => {
+			toml::Value::Array(values.into_iter().map(dates_to_strings).collect())
+		},
+		toml::Value::Table(table) => toml::Value::Table(
+			table
+				.into_iter()
+				.map(|(key, value)| (key, dates_to_strings(value)))
+				.collect(),
+		),
+		other => other,
+	}
+}
+
+/// Parse a TOML document, reading dates as strings.
+pub fn parse_toml<T: DeserializeOwned>(content: &str) -> Result<T> {
+	Ok(dates_to_strings(toml::from_str(content)?).try_into()?)
+}
+
+/// Read the config file, if there is one.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+	let Some(path) = path.filter(|path| path.exists()) else {
+		return Ok(Config::default());
+	};
+	let content =
+		fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+	parse_toml(&content).with_context(|| format!("Could not parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+	use clap::FromArgMatches;
+
+	use super::*;
+	use crate::GridRatios;
+
+	#[test]
+	fn the_command_line_wins_over_the_config_file() {
+		let config: Config = parse_toml(
+			r#"
+			[grid]
+			stroke = 3
+			padding = 5
+			border_unit = "shape"
+			"#,
+		)
+		.unwrap();
+		let matches = GridRatios::command()
+			.try_get_matches_from(["grid", "--stroke", "2"])
+			.unwrap();
+		let ratios = GridRatios::from_arg_matches(&matches).unwrap();
+		let ratios = apply(ratios, &config.grid, &matches).unwrap();
+		assert_eq!(ratios.stroke, 2);
+		assert_eq!(ratios.padding, 5);
+		assert_eq!(ratios.length, 15);
+		assert!(matches!(ratios.border_unit, crate::BorderUnit::Shape));
+	}
+
+	#[test]
+	fn unknown_options_are_an_error() {
+		let config: Config = parse_toml("[grid]\nstrok = 3").unwrap();
+		assert!(check_keys("grid", &config.grid, &[GridRatios::command()]).is_err());
+		assert!(check_keys("image", &config.image, &[]).is_ok());
+		assert!(parse_toml::<Config>("[grids]\nstroke = 3").is_err());
+		assert!(parse_toml::<Config>("[[marker]]\nname = \"Retirement\"").is_err());
+	}
+}
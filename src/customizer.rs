@@ -0,0 +1,897 @@
+use std::{
+	io::{self, BufWriter, Write},
+	path::Path,
+};
+
+use anyhow::Result;
+use clap::{Arg, ArgAction, Command, CommandFactory};
+
+use crate::{
+	calendar_image::Replacement, DrawingInfo, GridLabels, GridLayout, GridRatios, GridStyle,
+	LifeInfo, LogStyle,
+};
+
+/// Styles for the form and the preview.
+const STYLE: &str = r#"
+body {
+	margin: 0 auto;
+	max-width: 90rem;
+	padding: 1rem;
+	font-family: sans-serif;
+	display: flex;
+	flex-wrap: wrap;
+	gap: 1rem;
+	align-items: flex-start;
+}
+form {
+	flex: 0 1 24rem;
+}
+fieldset {
+	margin-bottom: 1rem;
+}
+label {
+	display: grid;
+	grid-template-columns: 10rem 1fr;
+	gap: 0.5rem;
+	align-items: center;
+	margin: 0.25rem 0;
+}
+label input[type="text"], label input[type="number"], label select {
+	width: 100%;
+	box-sizing: border-box;
+}
+label .color {
+	display: flex;
+	gap: 0.25rem;
+}
+label small {
+	grid-column: 2;
+	color: #666;
+}
+main {
+	flex: 1 1 40rem;
+}
+#preview svg {
+	width: 100%;
+	max-height: 80vh;
+}
+textarea {
+	width: 100%;
+	box-sizing: border-box;
+	font-family: monospace;
+}
+"#;
+
+/// Options for things the preview doesn't draw, which get no control on the page. The page lists
+/// them, along with the options for the layout and labels of grids.
+const NOT_PREVIEWED: [&str; 20] = [
+	"theme",
+	"fill_mode",
+	"color_birth",
+	"color_death",
+	"background",
+	"background_image",
+	"background_opacity",
+	"past_pattern",
+	"present_pattern",
+	"future_pattern",
+	"event_pattern",
+	"events",
+	"week_shape_file",
+	"present_progress",
+	"arrow",
+	"show_remaining",
+	"remaining_text",
+	"font_family",
+	"font_file",
+	"text_to_path",
+];
+
+/// Builds the form from `CONTROLS`, and redraws the preview and the exported settings whenever
+/// the form changes.
+///
+/// The preview follows the same sums and colors as the real images, but leaves out labels, markers
+/// and phases, along with everything in `NOT_PREVIEWED`. It counts the weeks of each year without
+/// looking at a calendar, and always draws logarithmic calendars left to right.
+const SCRIPT: &str = r##"
+const form = document.getElementById("options");
+const inputs = new Map();
+
+function addControl(control) {
+	const fieldset = document.getElementById(control.section);
+	const label = document.createElement("label");
+	const name = document.createElement("span");
+	name.textContent = control.long;
+	label.append(name);
+	let input;
+	if (control.kind === "flag") {
+		input = document.createElement("input");
+		input.type = "checkbox";
+		label.append(input);
+	} else if (control.kind === "choice") {
+		input = document.createElement("select");
+		for (const choice of control.choices) {
+			input.append(new Option(choice, choice, false, choice === control.value));
+		}
+		label.append(input);
+	} else {
+		input = document.createElement("input");
+		input.type = control.kind === "number" ? "number" : "text";
+		input.step = "any";
+		input.value = control.value;
+		if (control.kind === "color") {
+			const picker = document.createElement("input");
+			picker.type = "color";
+			picker.value = cssToHex(control.value || "#ffffff");
+			picker.addEventListener("input", () => {
+				input.value = picker.value;
+				update();
+			});
+			const both = document.createElement("span");
+			both.className = "color";
+			both.append(input, picker);
+			label.append(both);
+		} else {
+			label.append(input);
+		}
+	}
+	if (control.help) {
+		const help = document.createElement("small");
+		help.textContent = control.help;
+		label.append(help);
+	}
+	fieldset.append(label);
+	inputs.set(control.id, { control, input });
+}
+
+// Turn any CSS color into a hex code, or `null` if it isn't one.
+function cssToHex(color) {
+	const context = document.createElement("canvas").getContext("2d");
+	context.fillStyle = "#010203";
+	context.fillStyle = color;
+	const parsed = context.fillStyle;
+	if (parsed === "#010203" && color.trim().toLowerCase() !== "#010203") {
+		return null;
+	}
+	return parsed.startsWith("#") ? parsed : null;
+}
+
+// The value of an option, or `null` if it's left as it is by default.
+function changed(id) {
+	const { control, input } = inputs.get(id);
+	if (control.kind === "flag") {
+		return input.checked ? true : null;
+	}
+	const value = input.value.trim();
+	return value === "" || value === control.value ? null : value;
+}
+
+// The value of an option, falling back to its default.
+function value(id) {
+	const { control, input } = inputs.get(id);
+	if (control.kind === "flag") {
+		return input.checked;
+	}
+	const value = input.value.trim() || control.value;
+	return control.kind === "number" ? Number(value) : value;
+}
+
+function shellQuote(text) {
+	return /^[\w@%+=:,./#-]+$/.test(text) ? text : `'${text.replaceAll("'", `'"'"'`)}'`;
+}
+
+function tomlValue(control, value) {
+	if (control.kind === "flag" || control.kind === "number") {
+		return String(value);
+	}
+	return JSON.stringify(value);
+}
+
+function exportSettings() {
+	const drawing = document.getElementById("drawing").value;
+	const sections = { image: [], grid: [], log: [] };
+	const args = { image: [], grid: [], log: [] };
+	for (const [id, { control }] of inputs) {
+		const value = changed(id);
+		if (value === null) {
+			continue;
+		}
+		sections[control.section].push(`${id} = ${tomlValue(control, value)}`);
+		args[control.section].push(
+			control.kind === "flag" ? `--${control.long}` : `--${control.long}=${shellQuote(value)}`
+		);
+	}
+	const toml = Object.entries(sections)
+		.filter(([, lines]) => lines.length > 0)
+		.map(([section, lines]) => `[${section}]\n${lines.join("\n")}`)
+		.join("\n\n");
+	document.getElementById("toml").value = toml;
+	const life = [document.getElementById("birthday").value];
+	const lifespan = document.getElementById("lifespan").value;
+	if (lifespan !== "100") {
+		life.push(`--lifespan-years=${lifespan}`);
+	}
+	document.getElementById("command").value = [
+		"death-calendar",
+		...life,
+		"img",
+		...args.image,
+		drawing,
+		...args[drawing],
+	].join(" ");
+}
+
+// The same as `color::opposite`: flip the lightness in OKLab around the middle, and the hue too.
+function opposite(hex) {
+	const channels = [1, 3, 5].map((start) => parseInt(hex.slice(start, start + 2), 16) / 255);
+	const [r, g, b] = channels.map((x) => (x >= 0.04045 ? ((x + 0.055) / 1.055) ** 2.4 : x / 12.92));
+	const l_ = Math.cbrt(0.412165612 * r + 0.536275208 * g + 0.0514575653 * b);
+	const m_ = Math.cbrt(0.211859107 * r + 0.6807189584 * g + 0.107406579 * b);
+	const s_ = Math.cbrt(0.0883097947 * r + 0.2818474174 * g + 0.6302613616 * b);
+	const lightness = 0.2104542553 * l_ + 0.793617785 * m_ - 0.0040720468 * s_;
+	const a = -(1.9779984951 * l_ - 2.428592205 * m_ + 0.4505937099 * s_) / 2;
+	const bb = -(0.0259040371 * l_ + 0.7827717662 * m_ - 0.808675766 * s_) / 2;
+	const l = lightness < 0.57 ? 1 - lightness * 0.1 : (1 - lightness) * 0.1;
+	const l3 = (l + 0.3963377774 * a + 0.2158037573 * bb) ** 3;
+	const m3 = (l - 0.1055613458 * a - 0.0638541728 * bb) ** 3;
+	const s3 = (l - 0.0894841775 * a - 1.291485548 * bb) ** 3;
+	const linear = [
+		4.0767245293 * l3 - 3.3072168827 * m3 + 0.2307590544 * s3,
+		-1.2681437731 * l3 + 2.6093323231 * m3 - 0.341134429 * s3,
+		-0.0041119885 * l3 - 0.7034763098 * m3 + 1.7068625689 * s3,
+	];
+	const channel = (x) => {
+		const srgb = x >= 0.0031308 ? 1.055 * x ** (1 / 2.4) - 0.055 : 12.92 * x;
+		const byte = Math.floor(Math.min(Math.max(srgb, 0), 1) * 255 + 0.5);
+		return byte.toString(16).padStart(2, "0");
+	};
+	return `#${linear.map(channel).join("")}`;
+}
+
+function colors() {
+	const primary = cssToHex(value("color_primary")) || "#000000";
+	const secondary = cssToHex(value("color_secondary")) || opposite(primary);
+	const orPrimary = (id) => cssToHex(value(id)) || primary;
+	return {
+		primary,
+		secondary,
+		present: orPrimary("color_present"),
+		background: cssToHex(value("color_background")) || secondary,
+		stroke: orPrimary("color_stroke"),
+		text: orPrimary("color_text"),
+		accent: orPrimary("color_accent"),
+	};
+}
+
+// Whole weeks and years lived so far, with 52 weeks to a year like a grid calendar.
+function lived() {
+	const birthday = new Date(document.getElementById("birthday").value);
+	const today = new Date();
+	let years = today.getUTCFullYear() - birthday.getUTCFullYear();
+	const lastBirthday = new Date(birthday);
+	lastBirthday.setUTCFullYear(birthday.getUTCFullYear() + years);
+	if (lastBirthday > today) {
+		years -= 1;
+		lastBirthday.setUTCFullYear(lastBirthday.getUTCFullYear() - 1);
+	}
+	const days = (today - birthday) / 86400000;
+	const weeks = Math.min(Math.floor((today - lastBirthday) / 86400000 / 7), 51);
+	return { years, weeks: years * 52 + weeks, days };
+}
+
+const HEART = [
+	[[0.2, 0.7], [0.0, 0.5], [0.0, 0.3]],
+	[[0.0, 0.1], [0.15, 0.0], [0.28, 0.0]],
+	[[0.4, 0.0], [0.5, 0.1], [0.5, 0.2]],
+	[[0.5, 0.1], [0.6, 0.0], [0.72, 0.0]],
+	[[0.85, 0.0], [1.0, 0.1], [1.0, 0.3]],
+	[[1.0, 0.5], [0.8, 0.7], [0.5, 0.95]],
+];
+
+function shape(kind, place, cornerRadius, fill, stroke, strokeWidth) {
+	const { x, y, cx, cy, size } = place;
+	const paint = `fill="${fill}" stroke="${stroke}" stroke-width="${strokeWidth}"`;
+	const point = ([u, v]) => `${u * size + x} ${v * size + y}`;
+	switch (kind) {
+		case "circle":
+			return `<circle cx="${cx}" cy="${cy}" r="${Math.floor(size / 2)}" ${paint}/>`;
+		case "rounded-square":
+		case "square": {
+			const rx = kind === "rounded-square" ? ` rx="${Math.min(cornerRadius, Math.floor(size / 2))}"` : "";
+			return `<rect x="${x}" y="${y}" width="${size}" height="${size}"${rx} ${paint}/>`;
+		}
+		case "diamond":
+			return `<path d="M ${cx} ${y} L ${x + size} ${cy} L ${cx} ${y + size} L ${x} ${cy} Z" ${paint}/>`;
+		case "hexagon": {
+			const radius = size / Math.sqrt(3);
+			const corners = [0, 1, 2, 3, 4, 5].map((corner) => {
+				const angle = (corner * Math.PI) / 3;
+				return `${cx + radius * Math.cos(angle)} ${cy + radius * Math.sin(angle)}`;
+			});
+			return `<path d="M ${corners.join(" L ")} Z" ${paint}/>`;
+		}
+		case "heart": {
+			const curves = HEART.map((curve) => `C ${curve.map(point).join(" ")}`);
+			return `<path d="M ${point([0.5, 0.95])} ${curves.join(" ")} Z" ${paint}/>`;
+		}
+	}
+	return "";
+}
+
+function drawGrid(lifespan) {
+	const { primary, secondary, present, background, stroke } = colors();
+	const scale = value("scale_factor");
+	const kind = value("week_shape");
+	const strokeWidth = value("stroke") * scale * 2;
+	const padding = value("padding") * scale;
+	const inner = value("length") * 2 * scale + strokeWidth;
+	const outer = inner + padding * 2 + strokeWidth;
+	let [pitch, stagger, overhang] = [outer, 0, 0];
+	if (kind === "hexagon") {
+		pitch = Math.round((outer * Math.sqrt(3)) / 2);
+		stagger = Math.floor(outer / 2);
+		overhang = Math.max(Math.ceil(inner / Math.sqrt(3) - inner / 2) - padding, 0);
+	}
+	const border =
+		overhang +
+		(value("border_unit") === "shape" ? value("border") * outer : value("border") * scale);
+	const gutter = value("gutter") * scale;
+	const lineOffset = (line) => line * pitch + Math.floor(line / 10) * gutter;
+	const width = lineOffset(lifespan - 1) + outer + (border + padding) * 2;
+	const height = 51 * outer + outer + stagger + (border + padding) * 2;
+	const { weeks } = lived();
+	const cells = [];
+	for (let line = 0; line < lifespan; line += 1) {
+		for (let pos = 0; pos < 52; pos += 1) {
+			const slotX = border + padding + lineOffset(line);
+			const slotY = border + padding + pos * outer + (line % 2 === 1 ? stagger : 0);
+			const place = {
+				x: slotX + padding + Math.floor(strokeWidth / 2),
+				y: slotY + padding + Math.floor(strokeWidth / 2),
+				cx: slotX + Math.floor(outer / 2),
+				cy: slotY + Math.floor(outer / 2),
+				size: inner,
+			};
+			const number = line * 52 + pos;
+			const cornerRadius = value("corner_radius") * scale;
+			if (number === weeks) {
+				cells.push(shape(kind, place, cornerRadius, secondary, present, strokeWidth * 2));
+			} else {
+				const fill = number < weeks ? primary : secondary;
+				cells.push(shape(kind, place, cornerRadius, fill, stroke, strokeWidth));
+			}
+		}
+	}
+	return svg(width, height, background, cells);
+}
+
+function drawLog(lifespan) {
+	const { primary, background, stroke: strokeColor, text: textColor, accent } = colors();
+	const scale = value("scale_factor");
+	const ratio = value("width_height_ratio");
+	const fontSize = 24 * scale;
+	const textSize = (fontSize * String(lifespan).length) / 2;
+	const innerWidth = 6 * ratio * fontSize;
+	const strokeWidth = fontSize / 12;
+	const padding = textSize / 0.8 / 2;
+	const label = value("label").replace(/&/g, "&amp;").replace(/</g, "&lt;");
+	const paddingX = ((fontSize / 2) * [...value("label")].length) / 2;
+	const gapSize = fontSize / 4;
+	const start = value("perceived_start_age");
+	const felt = (years) => (years <= start ? years / start : 1 + Math.log(years / start));
+	const position = {
+		classic: (years) => 1 - ((lifespan + 1) ** (1 - years / lifespan) - 1) / lifespan,
+		linear: (years) => years / lifespan,
+		log: (years) => Math.log1p(years) / Math.log1p(lifespan),
+		power: (years) => (years / lifespan) ** value("exponent"),
+		perceived: (years) => felt(years) / felt(lifespan),
+	}[value("scale")];
+	const x = (years) => position(years) * innerWidth + paddingX;
+	// Ticks, then the arrow, then its label above the line, and the year labels below it
+	const labelOut = fontSize * 4 + gapSize;
+	const baseline = padding + labelOut + fontSize;
+	const top = baseline - fontSize;
+	// Other layouts get a note under the preview saying it isn't quite what they'll get
+	const note = value("layout") !== "horizontal";
+	const height = padding * 2 + labelOut + fontSize * (note ? 3.5 : 2) + gapSize * 2;
+	const stroke = `stroke="${strokeColor}" stroke-width="${strokeWidth}"`;
+	const text = (content, at, y) =>
+		`<text x="${at}" y="${y}" fill="${textColor}" font-size="${fontSize}px" text-anchor="middle" dominant-baseline="central">${content}</text>`;
+	const today = x(lived().days / 365.2425);
+	const labelY = baseline + gapSize + fontSize / 2;
+	const livedWidth = Math.min(Math.max(today, x(0)), x(lifespan)) - x(0);
+	const elements = [
+		`<rect x="${x(0)}" y="${top}" width="${livedWidth}" height="${fontSize}" fill="${primary}" fill-opacity="0.15"/>`,
+		`<polyline points="${x(0)} ${top},${x(0)} ${baseline},${x(lifespan)} ${baseline},${x(lifespan)} ${top}" fill="none" ${stroke}/>`,
+		`<line x1="${today}" x2="${today}" y1="${baseline - fontSize * 4}" y2="${baseline - fontSize * 2}" stroke="${accent}" stroke-width="${strokeWidth}"/>`,
+		text(label, today, baseline - labelOut - fontSize / 2),
+	];
+	if (note) {
+		elements.push(text(`Shown left to right, but drawn ${value("layout")}`, paddingX + innerWidth / 2, labelY + fontSize * 1.5));
+	}
+	const subTicks = (year) => {
+		for (const [parts, quarter] of [[12, 3], [4, 1]]) {
+			const alongs = Array.from({ length: parts + 1 }, (_, part) => x(year + part / parts));
+			if (alongs.slice(1).every((along, part) => along - alongs[part] >= gapSize)) {
+				for (let part = 1; part < parts; part += 1) {
+					const height = part % quarter === 0 ? fontSize / 2 : fontSize / 4;
+					elements.push(`<line x1="${alongs[part]}" x2="${alongs[part]}" y1="${baseline}" y2="${baseline - height}" stroke="${strokeColor}" stroke-width="${strokeWidth / 2}"/>`);
+				}
+				return;
+			}
+		}
+	};
+	let previous = -Infinity;
+	for (let year = 0; year <= lifespan; year += 1) {
+		const current = x(year);
+		const gap = (fontSize * String(year).length) / 1.618;
+		if (year === 0 || year === lifespan) {
+			elements.push(text(year, current, labelY));
+			previous = current;
+			if (year === 0) {
+				subTicks(year);
+			}
+		} else if (current - previous >= gap && x(lifespan) - current > gap) {
+			elements.push(`<line x1="${current}" x2="${current}" y1="${baseline}" y2="${top}" ${stroke}/>`);
+			elements.push(text(year, current, labelY));
+			previous = current;
+			subTicks(year);
+		}
+	}
+	return svg(paddingX * 2 + innerWidth, height, background, elements);
+}
+
+function svg(width, height, background, elements) {
+	return `<svg viewBox="0 0 ${width} ${height}" xmlns="http://www.w3.org/2000/svg"><rect width="${width}" height="${height}" fill="${background}"/>${elements.join("")}</svg>`;
+}
+
+function update() {
+	const lifespan = Math.max(Math.round(Number(document.getElementById("lifespan").value)), 1);
+	const drawing = document.getElementById("drawing").value;
+	document.getElementById("preview").innerHTML =
+		drawing === "grid" ? drawGrid(lifespan) : drawLog(lifespan);
+	exportSettings();
+}
+
+for (const control of CONTROLS) {
+	addControl(control);
+}
+form.addEventListener("input", update);
+form.addEventListener("change", update);
+for (const id of ["copy-command", "copy-toml"]) {
+	document.getElementById(id).addEventListener("click", () => {
+		navigator.clipboard.writeText(document.getElementById(id.slice(5)).value);
+	});
+}
+update();
+"##;
+
+/// Write text as a JavaScript string, so it's safe to put inside a `<script>`.
+fn js_string(text: &str) -> String {
+	let mut escaped = String::from("\"");
+	for c in text.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			'\n' => escaped.push_str("\\n"),
+			'<' => escaped.push_str("\\u003c"),
+			c => escaped.push(c),
+		}
+	}
+	escaped.push('"');
+	escaped
+}
+
+/// Describe a command line option as a JavaScript object the page builds a form control from.
+fn control(section: &str, arg: &Arg) -> String {
+	let id = arg.get_id().as_str();
+	let default = arg
+		.get_default_values()
+		.first()
+		.map(|value| value.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	let is_flag = matches!(arg.get_action(), ArgAction::SetTrue);
+	let choices = arg
+		.get_possible_values()
+		.iter()
+		.filter(|_| !is_flag)
+		.map(|value| js_string(value.get_name()))
+		.collect::<Vec<_>>();
+	let kind = if is_flag {
+		"flag"
+	} else if !choices.is_empty() {
+		"choice"
+	} else if id.starts_with("color") {
+		"color"
+	} else if default.parse::<f64>().is_ok() {
+		"number"
+	} else {
+		"text"
+	};
+	// Only the first line of the help, since the rest goes into more detail than fits.
+	let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+	format!(
+		"{{section: {}, id: {}, long: {}, kind: {}, value: {}, choices: [{}], help: {}}}",
+		js_string(section),
+		js_string(id),
+		js_string(arg.get_long().unwrap_or(id)),
+		js_string(kind),
+		js_string(if is_flag { "" } else { &default }),
+		choices.join(", "),
+		js_string(help.lines().next().unwrap_or_default()),
+	)
+}
+
+/// The commands whose options can have controls, with the part of the form each one goes in.
+fn sections() -> [(&'static str, Command); 4] {
+	[
+		("image", DrawingInfo::command()),
+		("grid", GridRatios::command()),
+		("grid", GridStyle::command()),
+		("log", LogStyle::command()),
+	]
+}
+
+/// All of the options the page has controls for, as a JavaScript array.
+fn controls() -> String {
+	let controls = sections()
+		.iter()
+		.flat_map(|(section, command)| {
+			command
+				.get_arguments()
+				.filter(|arg| !NOT_PREVIEWED.contains(&arg.get_id().as_str()))
+				.map(|arg| format!("\t{},", control(section, arg)))
+		})
+		.collect::<Vec<_>>();
+	format!("[\n{}\n]", controls.join("\n"))
+}
+
+/// The options the page has no controls for, as a list of `<code>` tags.
+fn left_out() -> String {
+	let not_previewed = sections().into_iter().flat_map(|(_, command)| {
+		command
+			.get_arguments()
+			.filter(|arg| NOT_PREVIEWED.contains(&arg.get_id().as_str()))
+			.cloned()
+			.collect::<Vec<_>>()
+	});
+	let grid = [GridLayout::command(), GridLabels::command()]
+		.into_iter()
+		.flat_map(|command| command.get_arguments().cloned().collect::<Vec<_>>());
+	not_previewed
+		.chain(grid)
+		.filter_map(|arg| arg.get_long().map(|long| format!("<code>--{long}</code>")))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Write a web page for trying out different looks for a calendar.
+fn write_page(out: &mut impl Write, life_info: &LifeInfo) -> Result<()> {
+	write!(
+		out,
+		"<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta \
+		 name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>Death calendar \
+		 customizer</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<form id=\"options\" \
+		 autocomplete=\"off\">\n<fieldset>\n<legend>Life</legend>\n<label><span>birthday</span><input \
+		 id=\"birthday\" type=\"date\" value=\"{}\"></label>\n<label><span>lifespan-years</span><input \
+		 id=\"lifespan\" type=\"number\" min=\"1\" value=\"{}\"></label>\n<label><span>calendar</span><select \
+		 id=\"drawing\"><option value=\"grid\">grid</option><option \
+		 value=\"log\">log</option></select></label>\n</fieldset>\n<fieldset \
+		 id=\"image\">\n<legend>Image</legend>\n</fieldset>\n<fieldset \
+		 id=\"grid\">\n<legend>Grid</legend>\n</fieldset>\n<fieldset \
+		 id=\"log\">\n<legend>Logarithmic</legend>\n</fieldset>\n<p id=\"left-out\">The preview \
+		 doesn't draw markers or phases, and there's nothing here for {}. Add them to the command \
+		 line or config.toml yourself.</p>\n</form>\n<main>\n<div \
+		 id=\"preview\"></div>\n<h2>Command line</h2>\n<textarea id=\"command\" rows=\"3\" \
+		 readonly></textarea>\n<button id=\"copy-command\" type=\"button\">Copy</button>\n<h2>config.toml</h2>\n<textarea \
+		 id=\"toml\" rows=\"10\" readonly></textarea>\n<button id=\"copy-toml\" \
+		 type=\"button\">Copy</button>\n</main>\n<script>\nconst CONTROLS = {};\n{SCRIPT}</script>\n</body>\n</html>\n",
+		life_info.birthday,
+		life_info.lifespan_years,
+		left_out(),
+		controls(),
+	)?;
+	Ok(())
+}
+
+/// Save the customizer page to a file, or print it to stdout.
+pub fn write(life_info: &LifeInfo, output: Option<&Path>) -> Result<()> {
+	if let Some(path) = output {
+		// Written beside the old page, which is only replaced once the new one is finished.
+		let (replacement, file) = Replacement::create(path)?;
+		let mut out = BufWriter::new(file);
+		write_page(&mut out, life_info)?;
+		out.into_inner().map_err(io::IntoInnerError::into_error)?;
+		replacement.put_in_place()?;
+	} else {
+		write_page(&mut io::stdout().lock(), life_info)?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::BTreeMap,
+		process::{Command as Process, Stdio},
+	};
+
+	use clap::Parser;
+	use svg::{node::element::tag::Type, parser::Event};
+
+	use super::*;
+	use crate::{calendar_image, config::Config, Cli, Commands};
+
+	/// Just enough of a browser for the script to build its form and draw the preview.
+	const FAKE_BROWSER: &str = r#"
+const context = {
+	style: "",
+	set fillStyle(color) {
+		if (/^#[0-9a-f]{6}$/i.test(color)) this.style = color.toLowerCase();
+	},
+	get fillStyle() { return this.style; },
+};
+const element = () => ({ value: "", checked: false, append() {}, addEventListener() {}, getContext: () => context });
+const elements = new Map();
+const document = {
+	getElementById: (id) => elements.get(id) ?? elements.set(id, element()).get(id),
+	createElement: element,
+};
+const Option = function () {};
+"#;
+
+	/// Someone who has lived all of their years, so the calendar is the same whenever it's drawn.
+	const LIFE: [&str; 3] = ["1900-01-01", "--lifespan-years", "60"];
+
+	/// The image the page previews for some options, or `None` without Node.js to run it.
+	fn preview(drawing: &str, options: &[(&str, &str)]) -> Option<String> {
+		let set = options
+			.iter()
+			.map(|(long, value)| {
+				format!(
+					"inputs.get({}).input.value = {};\n",
+					js_string(&long.replace('-', "_")),
+					js_string(value)
+				)
+			})
+			.collect::<String>();
+		let script = format!(
+			"{FAKE_BROWSER}document.getElementById(\"birthday\").value = \"{}\";\n\
+			 document.getElementById(\"lifespan\").value = \"{}\";\n\
+			 document.getElementById(\"drawing\").value = \"{drawing}\";\nconst CONTROLS = \
+			 {};\n{SCRIPT}{set}update();\nprocess.stdout.write(document.getElementById(\"preview\").\
+			 innerHTML);\n",
+			LIFE[0],
+			LIFE[2],
+			controls(),
+		);
+		let mut node = Process::new("node")
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.spawn()
+			.ok()?;
+		node.stdin.take()?.write_all(script.as_bytes()).unwrap();
+		let output = node.wait_with_output().unwrap();
+		assert!(output.status.success());
+		Some(String::from_utf8(output.stdout).unwrap())
+	}
+
+	/// The image `img` draws for the same options.
+	fn render(drawing: &str, options: &[(&str, &str)]) -> String {
+		let (image, drawing_options): (Vec<_>, Vec<_>) = options.iter().partition(|(long, _)| {
+			DrawingInfo::command()
+				.get_arguments()
+				.any(|arg| arg.get_long() == Some(long))
+		});
+		let args = |options: Vec<&(&str, &str)>| {
+			options
+				.into_iter()
+				.map(|(long, value)| format!("--{long}={value}"))
+				.collect::<Vec<_>>()
+		};
+		let cli = Cli::parse_from(
+			["death-calendar"]
+				.into_iter()
+				.chain(LIFE)
+				.map(String::from)
+				.chain(["img".to_string()])
+				.chain(args(image))
+				.chain([drawing.to_string()])
+				.chain(args(drawing_options)),
+		);
+		let Commands::Image {
+			drawing_type,
+			drawing_info,
+		} = cli.command
+		else {
+			unreachable!()
+		};
+		let life_info = LifeInfo {
+			birthday: cli.birthday.unwrap(),
+			lifespan_years: cli.lifespan_years,
+		};
+		let (image, _) = calendar_image::render_calendar(
+			&drawing_type,
+			&drawing_info,
+			&life_info,
+			&Config::default(),
+		)
+		.unwrap();
+		String::from_utf8(image).unwrap()
+	}
+
+	/// Each element in an image, with its attributes and text.
+	fn elements(image: &str) -> Vec<(String, BTreeMap<String, String>, String)> {
+		let mut elements: Vec<(String, BTreeMap<String, String>, String)> = Vec::new();
+		for event in svg::read(image).unwrap() {
+			match event {
+				Event::Tag(name, Type::Start | Type::Empty, attributes) => elements.push((
+					name.to_string(),
+					attributes
+						.iter()
+						.map(|(key, value)| (key.clone(), value.to_string()))
+						.collect(),
+					String::new(),
+				)),
+				Event::Text(text) => {
+					if let Some(element) = elements.last_mut() {
+						element.2 = text.trim().to_string();
+					}
+				},
+				_ => {},
+			}
+		}
+		elements
+	}
+
+	/// Whether two attributes are the same, with numbers allowed to be a hair apart.
+	fn alike(one: &str, other: &str) -> bool {
+		let split = |text: &str| {
+			text.split([' ', ','])
+				.filter(|part| !part.is_empty())
+				.map(str::to_string)
+				.collect::<Vec<_>>()
+		};
+		let (one, other) = (split(one), split(other));
+		one.len() == other.len()
+			&& one.iter().zip(&other).all(|(one, other)| {
+				match (one.parse::<f64>(), other.parse::<f64>()) {
+					(Ok(one), Ok(other)) => (one - other).abs() < 0.1,
+					_ => one == other,
+				}
+			})
+	}
+
+	#[test]
+	fn the_preview_matches_the_real_grid() {
+		for options in [
+			&[][..],
+			&[("week-shape", "hexagon")],
+			&[
+				("week-shape", "circle"),
+				("scale-factor", "2"),
+				("border", "3"),
+				("border-unit", "shape"),
+				("color-primary", "#336699"),
+			],
+			&[("week-shape", "heart"), ("stroke", "2"), ("padding", "3")],
+			&[("week-shape", "diamond"), ("gutter", "7"), ("length", "5")],
+			&[
+				("week-shape", "rounded-square"),
+				("corner-radius", "4"),
+				("color-stroke", "#ff0000"),
+				("color-background", "#eeeeee"),
+			],
+		] {
+			let Some(preview) = preview("grid", options) else {
+				// Without Node.js there's no running the page's script.
+				return;
+			};
+			let image = render("grid", options);
+			// Leave out the description, and the background, which the preview draws without a
+			// position.
+			let drop_extras = |image: &str| {
+				let mut elements = elements(image);
+				elements.retain(|(name, _, _)| !matches!(name.as_str(), "title" | "desc"));
+				elements.remove(1);
+				elements
+			};
+			let (preview, image) = (drop_extras(&preview), drop_extras(&image));
+			assert_eq!(preview.len(), image.len(), "{options:?}");
+			for (shown, drawn) in preview.iter().zip(&image) {
+				assert_eq!(shown.0, drawn.0, "{options:?}");
+				for (key, value) in &shown.1 {
+					let drawn = drawn.1.get(key);
+					assert!(
+						drawn.is_some_and(|drawn| alike(value, drawn)),
+						"{options:?}: {key} is {value} in the preview and {drawn:?} in the image"
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn the_preview_labels_the_years_like_the_real_log() {
+		for options in [
+			&[][..],
+			&[("scale", "linear")],
+			&[("scale", "log")],
+			&[("scale", "power"), ("exponent", "0.5")],
+			&[("scale", "perceived"), ("perceived-start-age", "5")],
+			&[("scale-factor", "2"), ("width-height-ratio", "3")],
+			&[("label", "Now")],
+		] {
+			let Some(preview) = preview("log", options) else {
+				return;
+			};
+			let image = render("log", options);
+			// The preview leaves out arrows and shading, and draws ticks a little differently,
+			// but the size of the image and where each label goes are the same.
+			let labels = |image: &str| {
+				elements(image)
+					.into_iter()
+					.filter(|(name, _, _)| name == "svg" || name == "text")
+					.map(|(name, attributes, text)| {
+						let place = if name == "svg" { "viewBox" } else { "x" };
+						(attributes[place].clone(), text)
+					})
+					.collect::<Vec<_>>()
+			};
+			let (preview, image) = (labels(&preview), labels(&image));
+			assert_eq!(preview.len(), image.len(), "{options:?}");
+			for (shown, drawn) in preview.iter().zip(&image) {
+				assert!(
+					alike(&shown.0, &drawn.0) && shown.1 == drawn.1,
+					"{options:?}: {shown:?} in the preview and {drawn:?} in the image"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn the_page_has_a_control_for_every_option() {
+		let life_info = LifeInfo {
+			birthday: gregorian::Date::new(2000, 1, 1).unwrap(),
+			lifespan_years: 80,
+		};
+		let mut page = Vec::new();
+		write_page(&mut page, &life_info).unwrap();
+		let page = String::from_utf8(page).unwrap();
+		assert!(page.contains("value=\"2000-01-01\""));
+		assert!(page.contains("value=\"80\""));
+		for id in [
+			"color_primary",
+			"stroke",
+			"padding",
+			"length",
+			"border",
+			"width_height_ratio",
+		] {
+			assert!(page.contains(&format!("id: \"{id}\"")), "{id} is missing");
+		}
+		assert!(page.contains(
+			"id: \"border_unit\", long: \"border-unit\", kind: \"choice\", value: \"pixel\", choices: \
+			 [\"pixel\", \"shape\"]"
+		));
+		assert!(page.contains("id: \"compact\", long: \"compact\", kind: \"flag\""));
+		for id in NOT_PREVIEWED {
+			assert!(
+				!page.contains(&format!("id: \"{id}\"")),
+				"{id} can't be previewed"
+			);
+		}
+		// The page says what it leaves out.
+		for long in ["theme", "font-file", "unit", "year-label-interval"] {
+			assert!(page.contains(&format!("<code>--{long}</code>")), "{long}");
+		}
+	}
+
+	#[test]
+	fn strings_cannot_close_the_script() {
+		assert_eq!(
+			js_string("a \"b\"\n</script>"),
+			r#""a \"b\"\n\u003c/script>""#
+		);
+	}
+}
@@ -1,34 +1,141 @@
+use std::fmt::Write;
+
 use anyhow::Result;
 use death_calendar::{
 	days_left, days_lived, death_day, lifespan_days, lifespan_months, lifespan_weeks, months_left,
 	months_lived, weeks_left, weeks_lived, years_left, years_lived,
 };
 use gregorian::Date;
+use serde::Serialize;
 
 #[allow(clippy::print_stdout)]
-#[allow(clippy::uninlined_format_args)]
 pub fn show(bday: Date, lifespan_years: u16) -> Result<()> {
+	print!("{}", report(bday, lifespan_years)?);
+	Ok(())
+}
+
+/// Everything `info` shows about a life, as lines of text.
+#[allow(clippy::uninlined_format_args)]
+pub fn report(bday: Date, lifespan_years: u16) -> Result<String> {
 	let years: i16 = lifespan_years.try_into()?;
 	let today: Date = Date::today_utc();
-	println!("Your birthday is {}.", bday);
-	println!();
-	println!("You will live for approximately:");
-	println!("- {} days", lifespan_days(bday, years));
-	println!("- {} weeks", lifespan_weeks(years));
-	println!("- {} months", lifespan_months(years));
-	println!("- {} years", years);
-	println!();
-	println!("You will probably die around {}.", death_day(bday, years));
-	println!("You have lived for:");
-	println!("- {} days", days_lived(today, bday));
-	println!("- {} weeks", weeks_lived(today, bday));
-	println!("- {} months", months_lived(today, bday));
-	println!("- {} years", years_lived(today, bday));
-	println!();
-	println!("You have remaining:");
-	println!("- {} days", days_left(today, bday, years).abs());
-	println!("- {} weeks", weeks_left(today, bday, years).abs());
-	println!("- {} months", months_left(today, bday, years).abs());
-	println!("- {} years", years_left(today, bday, years).abs());
-	Ok(())
+	let mut report = String::new();
+	writeln!(report, "Your birthday is {}.", bday)?;
+	writeln!(report)?;
+	writeln!(report, "You will live for approximately:")?;
+	writeln!(report, "- {} days", lifespan_days(bday, years))?;
+	writeln!(report, "- {} weeks", lifespan_weeks(years))?;
+	writeln!(report, "- {} months", lifespan_months(years))?;
+	writeln!(report, "- {} years", years)?;
+	writeln!(report)?;
+	writeln!(
+		report,
+		"You will probably die around {}.",
+		death_day(bday, years)
+	)?;
+	writeln!(report, "You have lived for:")?;
+	writeln!(report, "- {} days", days_lived(today, bday))?;
+	writeln!(report, "- {} weeks", weeks_lived(today, bday))?;
+	writeln!(report, "- {} months", months_lived(today, bday))?;
+	writeln!(report, "- {} years", years_lived(today, bday))?;
+	writeln!(report)?;
+	writeln!(report, "You have remaining:")?;
+	writeln!(report, "- {} days", days_left(today, bday, years).abs())?;
+	writeln!(report, "- {} weeks", weeks_left(today, bday, years).abs())?;
+	writeln!(report, "- {} months", months_left(today, bday, years).abs())?;
+	writeln!(report, "- {} years", years_left(today, bday, years).abs())?;
+	Ok(report)
+}
+
+/// An amount of time in each of the units `info` counts in.
+#[derive(Debug, Serialize)]
+pub struct Durations {
+	days: i32,
+	weeks: i32,
+	months: i32,
+	years: i32,
+}
+
+/// Everything `info` shows about a life.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+	birthday: Date,
+	lifespan_years: u16,
+	death_day: Date,
+	lifespan: Durations,
+	lived: Durations,
+	left: Durations,
+}
+
+/// The numbers in the report, for programs to read.
+pub fn stats(bday: Date, lifespan_years: u16, today: Date) -> Result<Stats> {
+	let years: i16 = lifespan_years.try_into()?;
+	Ok(Stats {
+		birthday: bday,
+		lifespan_years,
+		death_day: death_day(bday, years),
+		lifespan: Durations {
+			days: lifespan_days(bday, years),
+			weeks: lifespan_weeks(years),
+			months: lifespan_months(years),
+			years: years.into(),
+		},
+		lived: Durations {
+			days: days_lived(today, bday),
+			weeks: weeks_lived(today, bday),
+			months: months_lived(today, bday),
+			years: years_lived(today, bday),
+		},
+		left: Durations {
+			days: days_left(today, bday, years).abs(),
+			weeks: weeks_left(today, bday, years).abs(),
+			months: months_left(today, bday, years).abs(),
+			years: years_left(today, bday, years).abs(),
+		},
+	})
+}
+
+/// Write a number with a comma between each group of three digits, like 1,234,567.
+pub fn group_digits(number: i64) -> String {
+	let digits = number.unsigned_abs().to_string();
+	let mut grouped = String::new();
+	for (index, digit) in digits.chars().enumerate() {
+		if index > 0 && (digits.len() - index).is_multiple_of(3) {
+			grouped.push(',');
+		}
+		grouped.push(digit);
+	}
+	if number < 0 {
+		grouped.insert(0, '-');
+	}
+	grouped
+}
+
+/// A short description of how much of a life has been lived so far, for people who can't see
+/// the image.
+pub fn summary(bday: Date, lifespan_years: u16) -> Result<String> {
+	let years: i16 = lifespan_years.try_into()?;
+	let today = Date::today_utc();
+	let percent_lived = (f64::from(days_lived(today, bday)) * 100.0
+		/ f64::from(lifespan_days(bday, years)))
+	.clamp(0.0, 100.0);
+	Ok(format!(
+		"Born on {bday} and now aged {}, with {percent_lived:.1}% of an expected {years} years \
+		 lived and {} weeks left.",
+		years_lived(today, bday).max(0),
+		group_digits(weeks_left(today, bday, years).max(0).into())
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn digits_are_grouped_in_threes() {
+		assert_eq!(group_digits(0), "0");
+		assert_eq!(group_digits(999), "999");
+		assert_eq!(group_digits(1234), "1,234");
+		assert_eq!(group_digits(-1_234_567), "-1,234,567");
+	}
 }
@@ -0,0 +1,152 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use csscolorparser::Color;
+use gregorian::Date;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::config;
+
+/// Colors given to events that don't pick their own.
+static DEFAULT_COLORS: [&str; 8] = [
+	"#e15759", "#4e79a7", "#f28e2b", "#59a14f", "#b07aa1", "#edc948", "#76b7b2", "#ff9da7",
+];
+
+/// An event as it's written in an events file.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+struct EventEntry {
+	name: String,
+	date: Date,
+	/// Last day of the event, for events that last a while
+	end: Option<Date>,
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	color: Option<Color>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsFile {
+	events: Vec<EventEntry>,
+}
+
+/// Something that happened, or will happen, during a life.
+#[derive(Debug, Clone)]
+pub struct Event {
+	pub name: String,
+	/// The first day of the event
+	pub start: Date,
+	/// The first day after the event
+	pub end: Date,
+	pub color: Color,
+}
+
+impl Event {
+	/// Whether any part of the event happens between `start` and the day before `end`.
+	pub fn overlaps(&self, start: Date, end: Date) -> bool {
+		self.start < end && start < self.end
+	}
+}
+
+fn parse_toml(content: &str) -> Result<Vec<EventEntry>> {
+	Ok(config::parse_toml::<EventsFile>(content)?.events)
+}
+
+fn parse_csv(content: &str) -> Result<Vec<EventEntry>> {
+	csv::ReaderBuilder::new()
+		.trim(csv::Trim::All)
+		.from_reader(content.as_bytes())
+		.deserialize()
+		.map(|entry| Ok(entry?))
+		.collect()
+}
+
+/// Read a list of events from a TOML or CSV file.
+///
+/// A TOML file has an `[[events]]` table for each event. A CSV file has a header with `name`,
+/// `date`, `end` and `color` columns. Only the name and date are required.
+pub fn load(path: &Path) -> Result<Vec<Event>> {
+	let content =
+		fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+	let entries = match path.extension().and_then(|ext| ext.to_str()) {
+		Some(ext) if ext.eq_ignore_ascii_case("csv") => parse_csv(&content),
+		Some(ext) if ext.eq_ignore_ascii_case("toml") => parse_toml(&content),
+		_ => bail!("Events must be in a file ending with .toml or .csv"),
+	}
+	.with_context(|| format!("Could not read events from {}", path.display()))?;
+
+	entries
+		.into_iter()
+		.zip(DEFAULT_COLORS.iter().cycle())
+		.map(|(entry, default_color)| {
+			let end = entry.end.unwrap_or(entry.date);
+			if end < entry.date {
+				bail!("The event \"{}\" ends before it starts", entry.name);
+			}
+			Ok(Event {
+				name: entry.name,
+				start: entry.date,
+				end: end.next(),
+				color: entry
+					.color
+					.map_or_else(|| csscolorparser::parse(default_color), Ok)?,
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn date(year: i16, month: u8, day: u8) -> Date {
+		Date::new(year, month, day).unwrap()
+	}
+
+	#[test]
+	fn toml_and_csv_files_read_the_same() {
+		let toml = parse_toml(
+			r#"
+			[[events]]
+			name = "Graduation"
+			date = 2012-06-01
+
+			[[events]]
+			name = "First job"
+			date = "2012-09-01"
+			end = "2016-05-31"
+			color = "orange"
+			"#,
+		)
+		.unwrap();
+		let csv = parse_csv(
+			"name, date, end, color\nGraduation, 2012-06-01, ,\nFirst job, 2012-09-01, 2016-05-31, \
+			 orange\n",
+		)
+		.unwrap();
+		for entries in [toml, csv] {
+			assert_eq!(entries.len(), 2);
+			assert_eq!(entries[0].date, date(2012, 6, 1));
+			assert_eq!(entries[0].end, None);
+			assert_eq!(entries[1].end, Some(date(2016, 5, 31)));
+			assert_eq!(
+				entries[1].color.as_ref().map(Color::to_hex_string),
+				Some("#ffa500".to_string())
+			);
+		}
+	}
+
+	#[test]
+	fn single_day_events_overlap_their_own_week() {
+		let event = Event {
+			name: "Graduation".to_string(),
+			start: date(2012, 6, 1),
+			end: date(2012, 6, 2),
+			color: Color::new(0.0, 0.0, 0.0, 1.0),
+		};
+		assert!(event.overlaps(date(2012, 5, 28), date(2012, 6, 4)));
+		assert!(event.overlaps(date(2012, 6, 1), date(2012, 6, 8)));
+		assert!(!event.overlaps(date(2012, 5, 25), date(2012, 6, 1)));
+		assert!(!event.overlaps(date(2012, 6, 2), date(2012, 6, 9)));
+	}
+}
@@ -1,16 +1,35 @@
-use std::{error::Error as StdError, path::PathBuf};
+use std::{error::Error as StdError, fs, path::PathBuf};
 
 mod calendar_image;
+mod color;
+mod config;
+mod customizer;
 mod death_info;
-use anyhow::Result;
-use calendar_image::grid::{BorderUnit, SvgShape};
-use clap::{value_parser, Parser};
+mod events;
+mod markers;
+mod phases;
+mod server;
+mod theme;
+use anyhow::{anyhow, Context, Result};
+use calendar_image::{
+	grid::{Alignment, BorderUnit, GridUnit, Orientation, SvgShape, Weekday},
+	hourglass::{BulbShape, GrainUnit},
+	logarithmic::{ArrowHead, LogLayout, LogScale},
+	pattern::Pattern,
+	Backdrop, Background, FillMode, Format,
+};
+use clap::{value_parser, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use color::Gradient;
+use config::{Config, CssColor};
 use csscolorparser::{parse as parse_css_color, Color};
 use directories::ProjectDirs;
+use events::Event;
 use gregorian::Date;
+use markers::Marker;
 use once_cell::sync::Lazy;
+use phases::Phase;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DisplayFromStr};
+use serde_with::serde_as;
 
 static QUALIFIER: &str = "xyz";
 static ORGANIZATION: &str = "Westrom";
@@ -41,6 +60,12 @@ static CONFIG_FILE_PATH: Lazy<Option<PathBuf>> = Lazy::new(|| {
 	Some(file)
 });
 
+static THEMES_DIR_PATH: Lazy<Option<PathBuf>> = Lazy::new(|| {
+	let mut dir = PROJECT_DIRS.as_ref().ok()?.preference_dir().to_path_buf();
+	dir.push("themes");
+	Some(dir)
+});
+
 static BIRTHDAY_FILE_PATH: Lazy<Option<PathBuf>> = Lazy::new(|| {
 	let mut file = PROJECT_DIRS.as_ref().ok()?.data_dir().to_path_buf();
 	file.push("birthday");
@@ -65,20 +90,55 @@ calculate your estimated time of death the same way each time.")
 struct Cli {
 	#[clap(subcommand)]
 	command: Commands,
-	#[clap(flatten)]
-	life_info: LifeInfo,
+	/// A birthday in `YYYY-MM-DD` format
+	///
+	/// Read from the birthday file when it's left out.
+	birthday: Option<Date>,
+	/// Expected lifespan in years
+	#[clap(short, long, default_value_t = 100)]
+	lifespan_years: u16,
+	/// Read settings from this file instead of the usual config file
+	#[clap(long, global = true)]
+	config: Option<PathBuf>,
+}
+
+/// The birthday and lifespan to draw, reading the birthday file if there's no birthday on the
+/// command line.
+fn life_info(birthday: Option<Date>, lifespan_years: u16) -> Result<LifeInfo> {
+	let birthday = if let Some(birthday) = birthday {
+		birthday
+	} else {
+		let path = BIRTHDAY_FILE_PATH
+			.as_ref()
+			.context("Give a birthday, since there's nowhere to find the birthday file")?;
+		let content = fs::read_to_string(path).with_context(|| {
+			format!(
+				"Give a birthday, or put one in {} to use it every time",
+				path.display()
+			)
+		})?;
+		content
+			.trim()
+			.parse()
+			.map_err(|error| anyhow!("{error}"))
+			.with_context(|| format!("Could not read a birthday from {}", path.display()))?
+	};
+	Ok(LifeInfo {
+		birthday,
+		lifespan_years,
+	})
 }
 
 /// Information about a person's life.
-#[derive(Parser, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LifeInfo {
 	/// A birthday in `YYYY-MM-DD` format
 	birthday: Date,
 	/// Expected lifespan in years
-	#[clap(short, long, default_value_t = 100)]
 	lifespan_years: u16,
 }
 
+#[allow(clippy::large_enum_variant)] // It's only built once, when parsing the command line
 #[derive(Parser, Debug, Serialize, Deserialize)]
 enum Commands {
 	/// Print info about your ultimate demise
@@ -91,6 +151,25 @@ enum Commands {
 		#[clap(flatten)]
 		drawing_info: DrawingInfo,
 	},
+	/// Make a web page for trying out different looks for your calendar
+	///
+	/// The page shows a preview as you change the options, and gives you the command line or
+	/// config file settings to draw the same calendar.
+	Customize {
+		/// Save the page to a file instead of printing to stdout
+		#[clap(short, long)]
+		output: Option<PathBuf>,
+	},
+	/// Draw calendars for anyone who asks over HTTP
+	///
+	/// Images are at paths like `/grid.svg?birthday=1990-01-01&week-shape=circle`, with the
+	/// same options as `img` in the query, and `/info.json` has the numbers `info` prints. Ask for
+	/// `.html` instead of `.svg` to get a web page.
+	Serve {
+		/// Address to listen on
+		#[clap(long, default_value = "127.0.0.1:8080")]
+		bind: String,
+	},
 }
 
 /// Information about how to render an image.
@@ -107,16 +186,122 @@ pub struct DrawingInfo {
 	///
 	/// You can use a string containing any valid CSS3 color.
 	/// Uses [csscolorparser](https://crates.io/crates/csscolorparser).
-	#[serde_as(as = "DisplayFromStr")]
+	#[serde_as(as = "CssColor")]
 	#[clap(long, value_parser(parse_css_color), default_value = "black")]
 	color_primary: Color,
 	/// Add a secondary color.
-	#[serde_as(as = "Option<DisplayFromStr>")]
+	#[serde_as(as = "Option<CssColor>")]
 	#[clap(long, value_parser(parse_css_color))]
 	color_secondary: Option<Color>,
+	/// Add a color to pick out the present moment.
+	///
+	/// Defaults to the primary color.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_present: Option<Color>,
+	/// Add a color for the background.
+	///
+	/// Defaults to the secondary color.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_background: Option<Color>,
+	/// Add a color for outlines and lines.
+	///
+	/// Defaults to the primary color.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_stroke: Option<Color>,
+	/// Add a color for labels.
+	///
+	/// Defaults to the primary color.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_text: Option<Color>,
+	/// Add a color for arrows that point things out, like today on a logarithmic calendar.
+	///
+	/// Defaults to the primary color.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_accent: Option<Color>,
+	/// What to put behind the calendar
+	#[clap(long, value_enum, default_value_t = Background::Color)]
+	background: Background,
+	/// Picture to put behind the calendar with `--background image`.
+	///
+	/// It can be a PNG, JPEG, GIF, WebP or SVG file, and is copied into the image, cropped to
+	/// cover all of it.
+	#[clap(long)]
+	background_image: Option<PathBuf>,
+	/// How much the background hides what's behind it, from 0 to 1
+	#[clap(long, default_value_t = 1.0)]
+	background_opacity: f64,
+	/// Use the colors of a theme for anything not set with the options above.
+	///
+	/// The bundled themes are nord, solarized-dark, gruvbox and paper. Your own themes go in the
+	/// `themes` folder next to the config file, as TOML files with any of `background`, `past`,
+	/// `future`, `present`, `stroke`, `text` and `accent`, and a list of `phases` colors.
+	#[clap(long)]
+	theme: Option<String>,
+	/// Color the past and future with one color each, or with gradients across the lifespan
+	#[clap(long, value_enum, default_value_t = FillMode::Solid)]
+	fill_mode: FillMode,
+	/// Color on the day you were born, with `--fill-mode gradient`.
+	///
+	/// Defaults to halfway between the primary and secondary colors.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_birth: Option<Color>,
+	/// Color on the expected day of death, with `--fill-mode gradient`.
+	///
+	/// Defaults to halfway between the primary and secondary colors, like `--color-birth`.
+	#[serde_as(as = "Option<CssColor>")]
+	#[clap(long, value_parser(parse_css_color))]
+	color_death: Option<Color>,
+	/// Pattern for the shapes you've already lived, like `hatching` to tell them apart when
+	/// printing in black and white.
+	///
+	/// Patterns are drawn over the shape's color, in the stroke or background color, whichever
+	/// stands out more against it.
+	#[clap(long, value_enum, default_value_t = Pattern::Solid)]
+	past_pattern: Pattern,
+	/// Pattern for the shape you're living in now, drawn in the present color
+	#[clap(long, value_enum, default_value_t = Pattern::Solid)]
+	present_pattern: Pattern,
+	/// Pattern for the shapes still to come
+	#[clap(long, value_enum, default_value_t = Pattern::Solid)]
+	future_pattern: Pattern,
+	/// Pattern for events, drawn in their own colors
+	#[clap(long, value_enum, default_value_t = Pattern::Solid)]
+	event_pattern: Pattern,
+	/// Stop with an error instead of warning when colors are hard to tell apart.
+	///
+	/// Colors need the contrast WCAG asks for, and a theme's colors need to look different to
+	/// someone with deuteranopia, the most common kind of color blindness.
+	#[clap(long)]
+	strict: bool,
 	/// Save SVG to a file instead of printing to stdout
+	///
+	/// Files ending with `.svgz` are compressed with gzip.
 	#[clap(short, long)]
 	output: Option<PathBuf>,
+	/// Draw each shape once and reuse it, with its colors in a style sheet.
+	///
+	/// This makes images with lots of shapes much smaller and quicker to open.
+	#[clap(long)]
+	compact: bool,
+	/// Leave out the line breaks between elements
+	#[clap(long)]
+	minify: bool,
+	/// Save the image as it is, or in a web page where you can hover over and click on it
+	#[clap(long, value_enum, default_value_t = Format::Svg)]
+	format: Format,
+	/// Mark events from your life on the calendar.
+	///
+	/// Events are read from a TOML file with an `[[events]]` table for each event, or a CSV
+	/// file with a header row. Each event has a `name` and a `date`, and can also have an `end`
+	/// date and a `color`.
+	#[clap(long)]
+	events: Option<PathBuf>,
 }
 
 /// Information about how to render an image with no optional fields.
@@ -124,6 +309,25 @@ pub struct DrawingInfoValidated {
 	scale_factor: u32,
 	color_primary: Color,
 	color_secondary: Color,
+	color_present: Color,
+	color_background: Color,
+	background: Backdrop,
+	background_opacity: f64,
+	color_stroke: Color,
+	color_text: Color,
+	color_accent: Color,
+	events: Vec<Event>,
+	phases: Vec<Phase>,
+	markers: Vec<Marker>,
+	/// Colors across the lifespan, when the past and future aren't filled with one color each
+	gradient: Option<Gradient>,
+	past_pattern: Pattern,
+	present_pattern: Pattern,
+	future_pattern: Pattern,
+	event_pattern: Pattern,
+	compact: bool,
+	/// Whether the image is going in a web page, where its shapes can say which days they are
+	interactive: bool,
 }
 
 #[non_exhaustive]
@@ -133,18 +337,91 @@ pub enum Drawing {
 	Grid {
 		#[clap(flatten)]
 		grid_ratios: GridRatios,
-		#[clap(long, value_enum, default_value_t = SvgShape::Square)]
-		/// Shape used to represent a week
-		week_shape: SvgShape,
+		#[clap(flatten)]
+		style: GridStyle,
+		#[clap(flatten)]
+		layout: GridLayout,
+		#[clap(flatten)]
+		labels: GridLabels,
 	},
 	#[clap(id = "log")]
 	/// Generate an image of a logarithmic calendar
 	Logarithmic {
-		#[clap(long, default_value_t = 8.0)]
-		width_height_ratio: f64,
+		#[clap(flatten)]
+		style: LogStyle,
+	},
+	/// Generate an image of an hourglass, with the time you have left in the upper bulb
+	Hourglass {
+		#[clap(flatten)]
+		style: HourglassStyle,
 	},
 }
 
+/// Information about how to draw a logarithmic calendar.
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct LogStyle {
+	#[clap(long, value_enum, default_value_t = LogLayout::Horizontal)]
+	/// Which way the line goes
+	layout: LogLayout,
+	#[clap(long, default_value_t = 8.0)]
+	/// How many times longer the calendar should be than it is across
+	///
+	/// Vertical calendars get taller, and radial ones get bigger around.
+	width_height_ratio: f64,
+	#[clap(long, value_enum, default_value_t = LogScale::Classic)]
+	/// How the years are spread along the line
+	scale: LogScale,
+	#[clap(long, default_value_t = 0.5)]
+	/// Power to raise your share of your lifespan to, for the power scale
+	///
+	/// Numbers under 1 give the early years more room, and numbers over 1 give it to the later
+	/// ones.
+	exponent: f64,
+	#[clap(long, default_value_t = 3.0)]
+	/// Age when years start to feel shorter, for the perceived scale
+	///
+	/// Every year before it feels as long as that one, since the early years are hard to remember.
+	perceived_start_age: f64,
+	#[clap(long, default_value = "You Are Here")]
+	/// What to call today
+	///
+	/// Other days can be marked with `[[markers]]` in the config file, each with a `name`, an
+	/// `age` or a `date`, and optionally a `color` and an `arrow`.
+	label: String,
+	#[clap(long, value_enum, default_value_t = ArrowHead::Triangle)]
+	/// What the end of the arrow pointing at today looks like
+	arrow: ArrowHead,
+	#[clap(long)]
+	/// Say how many years you've lived and how many are left, under the line
+	show_remaining: bool,
+	#[clap(long, default_value = "{lived} years lived / {left} left")]
+	/// What to say with `--show-remaining`, where `{lived}` and `{left}` are the numbers of years
+	remaining_text: String,
+	#[clap(long)]
+	/// Font family for the text, which is up to whatever shows the image if it's left out
+	font_family: Option<String>,
+	#[clap(long)]
+	/// Font file to measure the text with, so it's laid out to fit the letters it's drawn with
+	///
+	/// Its family is used for the text when `--font-family` is left out.
+	font_file: Option<PathBuf>,
+	#[clap(long)]
+	/// Draw the text as the outlines of the letters in `--font-file`, so it looks the same
+	/// everywhere
+	text_to_path: bool,
+}
+
+/// Information about how to draw an hourglass.
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct HourglassStyle {
+	#[clap(long, value_enum, default_value_t = GrainUnit::Month)]
+	/// Amount of time represented by each grain of sand
+	grain_unit: GrainUnit,
+	#[clap(long, value_enum, default_value_t = BulbShape::Cone)]
+	/// Shape of the bulbs holding the sand
+	bulb_shape: BulbShape,
+}
+
 /// Information about how to draw a grid calendar.
 #[derive(Parser, Debug, Serialize, Deserialize)]
 pub struct GridRatios {
@@ -160,19 +437,164 @@ pub struct GridRatios {
 	#[clap(long, default_value_t = 3)]
 	/// How much space should be around the grid?
 	border: u32,
-	#[clap(long, default_value_t = BorderUnit::Pixel)]
+	#[clap(long, value_enum, ignore_case = true, default_value_t = BorderUnit::Pixel)]
 	/// Should the border be measured in pixels or the shape?
 	border_unit: BorderUnit,
+	#[clap(long, default_value_t = 4)]
+	/// How much extra space should separate groups of shapes?
+	gutter: u32,
+}
+
+/// Information about how to draw the shapes of a grid calendar.
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct GridStyle {
+	#[clap(long, alias = "shape", value_enum, default_value_t = SvgShape::Square)]
+	/// Shape used to represent a week
+	week_shape: SvgShape,
+	#[clap(long, default_value_t = 4)]
+	/// How round should the corners of a rounded square be?
+	corner_radius: u32,
+	#[clap(long, conflicts_with = "week_shape")]
+	/// Draw each week with the paths from this SVG file instead of a built-in shape
+	week_shape_file: Option<PathBuf>,
+	#[clap(long)]
+	/// Partly fill in the present shape to show how much of it has passed
+	present_progress: bool,
+}
+
+/// Information about how to arrange the shapes of a grid calendar.
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct GridLayout {
+	#[clap(long, value_enum, default_value_t = GridUnit::Week)]
+	/// Amount of time represented by each shape
+	unit: GridUnit,
+	#[clap(long, value_enum, default_value_t = Alignment::Compressed)]
+	/// How each line of the grid lines up with the calendar
+	align: Alignment,
+	#[clap(long, value_enum, default_value_t = Weekday::Monday)]
+	/// First day of each week when lines are aligned to real years
+	week_start: Weekday,
+	#[clap(long, value_enum, default_value_t = Orientation::Columns)]
+	/// Should each year be a column or a row?
+	orientation: Orientation,
+	#[clap(long, value_parser(value_parser!(u32).range(1..)))]
+	/// How many shapes should go in a line before starting the next one?
+	///
+	/// Defaults to a year's worth of shapes, or 10 years when each shape is a year.
+	wrap: Option<u32>,
+	#[clap(long, default_value_t = 10)]
+	/// Put a gutter between the lines of the grid every N years, or never if N is 0
+	year_gutter_interval: u32,
+	#[clap(long, value_parser(value_parser!(u32).range(1..)))]
+	/// Put a gutter along the lines of the grid every N shapes, like 13 for quarters of a year
+	cell_gutter_interval: Option<u32>,
+}
+
+/// Information about how to label the axes of a grid calendar.
+#[derive(Parser, Debug, Serialize, Deserialize)]
+pub struct GridLabels {
+	#[clap(long)]
+	/// Label your age next to the lines of the grid every N years
+	year_label_interval: Option<u32>,
+	#[clap(long)]
+	/// Number the shapes along the lines of the grid every N shapes
+	cell_label_interval: Option<u32>,
+	#[clap(long, default_value = "sans-serif")]
+	/// Font family used for the labels
+	label_font_family: String,
+	#[clap(long, default_value_t = 12)]
+	/// Font size of the labels in pixels, multiplied by the scale factor
+	label_font_size: u32,
+	#[clap(long)]
+	/// Give each shape a tooltip with the dates it covers and how old you are then
+	cell_tooltips: bool,
+}
+
+/// Fill in the options of an image that weren't given on the command line from the config file.
+fn apply_config(
+	config: &Config,
+	drawing_type: Drawing,
+	drawing_info: DrawingInfo,
+	matches: &ArgMatches,
+) -> Result<(Drawing, DrawingInfo)> {
+	config::check_keys("image", &config.image, &[DrawingInfo::command()])?;
+	config::check_keys(
+		"grid",
+		&config.grid,
+		&[
+			GridRatios::command(),
+			GridStyle::command(),
+			GridLayout::command(),
+			GridLabels::command(),
+		],
+	)?;
+	config::check_keys("log", &config.log, &[LogStyle::command()])?;
+	config::check_keys("hourglass", &config.hourglass, &[HourglassStyle::command()])?;
+
+	let drawing_info = config::apply(drawing_info, &config.image, matches)?;
+	// A theme's colors go under the config file's as well as the command line's.
+	let drawing_info = match drawing_info.theme.clone() {
+		Some(name) => {
+			let mut colors = theme::load(&name)?.options();
+			colors.retain(|option, _| !config.image.contains_key(option));
+			config::apply(drawing_info, &colors, matches)?
+		},
+		None => drawing_info,
+	};
+	let (_, matches) = matches
+		.subcommand()
+		.context("An image needs to know what kind of calendar to draw")?;
+	let drawing_type = match drawing_type {
+		Drawing::Grid {
+			grid_ratios,
+			style,
+			layout,
+			labels,
+		} => Drawing::Grid {
+			grid_ratios: config::apply(grid_ratios, &config.grid, matches)?,
+			style: config::apply(style, &config.grid, matches)?,
+			layout: config::apply(layout, &config.grid, matches)?,
+			labels: config::apply(labels, &config.grid, matches)?,
+		},
+		Drawing::Logarithmic { style } => Drawing::Logarithmic {
+			style: config::apply(style, &config.log, matches)?,
+		},
+		Drawing::Hourglass { style } => Drawing::Hourglass {
+			style: config::apply(style, &config.hourglass, matches)?,
+		},
+	};
+	Ok((drawing_type, drawing_info))
 }
 
 fn main() -> Result<()> {
-	let cli = Cli::parse();
-	let life_info = cli.life_info;
+	let matches = Cli::command().get_matches();
+	let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+	let config = config::load(cli.config.as_deref().or(CONFIG_FILE_PATH.as_deref()))?;
+	let life_info = || life_info(cli.birthday, cli.lifespan_years);
 	match cli.command {
-		Commands::Info => death_info::show(life_info.birthday, life_info.lifespan_years),
+		Commands::Info => {
+			let life_info = life_info()?;
+			death_info::show(life_info.birthday, life_info.lifespan_years)
+		},
 		Commands::Image {
 			drawing_type,
 			drawing_info,
-		} => calendar_image::draw_calendar(drawing_type, drawing_info, &life_info),
+		} => {
+			let life_info = life_info()?;
+			let (_, image_matches) = matches
+				.subcommand()
+				.context("The image command has no options")?;
+			let (drawing_type, drawing_info) =
+				apply_config(&config, drawing_type, drawing_info, image_matches)?;
+			let warnings =
+				calendar_image::draw_calendar(drawing_type, drawing_info, &life_info, &config)?;
+			#[allow(clippy::print_stderr)]
+			for warning in warnings {
+				eprintln!("Warning: {warning}");
+			}
+			Ok(())
+		},
+		Commands::Customize { output } => customizer::write(&life_info()?, output.as_deref()),
+		Commands::Serve { bind } => server::run(&bind, &config),
 	}
 }
@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+use csscolorparser::Color;
+use gregorian::{Date, DateResultExt};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::calendar_image::logarithmic::ArrowHead;
+
+/// A marker as it's written in the config file.
+///
+/// Markers are put at an age or on a date, like "Retirement" at 65.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkerEntry {
+	name: String,
+	age: Option<i16>,
+	date: Option<Date>,
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	color: Option<Color>,
+	#[serde(default)]
+	arrow: ArrowHead,
+}
+
+/// A day to point out on a logarithmic calendar, with an arrow and a label.
+#[derive(Debug, Clone)]
+pub struct Marker {
+	pub name: String,
+	pub date: Date,
+	pub color: Color,
+	pub arrow: ArrowHead,
+}
+
+/// Work out which day each marker is on, and give markers without a color `default_color`.
+pub fn resolve(entries: &[MarkerEntry], bday: Date, default_color: &Color) -> Result<Vec<Marker>> {
+	entries
+		.iter()
+		.map(|entry| {
+			let date = match (entry.date, entry.age) {
+				(Some(_), Some(_)) => {
+					bail!(
+						"The marker \"{}\" can't have both `date` and `age`",
+						entry.name
+					)
+				},
+				(Some(date), None) => date,
+				(None, Some(age)) => bday.add_years(age).or_prev_valid(),
+				(None, None) => bail!("The marker \"{}\" needs a `date` or an `age`", entry.name),
+			};
+			Ok(Marker {
+				name: entry.name.clone(),
+				date,
+				color: entry.color.clone().unwrap_or_else(|| default_color.clone()),
+				arrow: entry.arrow,
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn markers_can_use_ages_or_dates() {
+		let config: crate::config::Config = crate::config::parse_toml(
+			r#"
+			[[markers]]
+			name = "Retirement at 65"
+			age = 65
+			arrow = "dot"
+
+			[[markers]]
+			name = "Kids leave home"
+			date = 2048-09-01
+			color = "teal"
+			"#,
+		)
+		.unwrap();
+		let bday = Date::new(2000, 2, 29).unwrap();
+		let black = Color::new(0.0, 0.0, 0.0, 1.0);
+		let markers = resolve(&config.markers, bday, &black).unwrap();
+		assert_eq!(markers[0].date, Date::new(2065, 2, 28).unwrap());
+		assert_eq!(markers[0].color.to_hex_string(), "#000000");
+		assert!(matches!(markers[0].arrow, ArrowHead::Dot));
+		assert_eq!(markers[1].date, Date::new(2048, 9, 1).unwrap());
+		assert_eq!(markers[1].color.to_hex_string(), "#008080");
+		assert!(matches!(markers[1].arrow, ArrowHead::Triangle));
+
+		let config: crate::config::Config =
+			crate::config::parse_toml("[[markers]]\nname = \"Someday\"").unwrap();
+		assert!(resolve(&config.markers, bday, &black).is_err());
+	}
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+use csscolorparser::Color;
+use gregorian::{Date, DateResultExt};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+/// Colors given to phases that don't pick their own.
+static DEFAULT_COLORS: [&str; 6] = [
+	"#59a14f", "#4e79a7", "#f28e2b", "#b07aa1", "#76b7b2", "#edc948",
+];
+
+/// A phase as it's written in the config file.
+///
+/// Phases can start and end at an age or on a date. An age is counted up to, but not including,
+/// that birthday, and a date is counted up to and including that day.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseEntry {
+	name: String,
+	from_age: Option<i16>,
+	to_age: Option<i16>,
+	from: Option<Date>,
+	to: Option<Date>,
+	/// Color of the part of the phase you've already lived
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	past_color: Option<Color>,
+	/// Color of the part of the phase that's still to come
+	#[serde_as(as = "Option<DisplayFromStr>")]
+	future_color: Option<Color>,
+}
+
+/// A named stretch of a life, with its own colors.
+#[derive(Debug, Clone)]
+pub struct Phase {
+	pub name: String,
+	/// The first day of the phase
+	pub start: Date,
+	/// The first day after the phase
+	pub end: Date,
+	pub color_past: Color,
+	pub color_future: Color,
+}
+
+impl Phase {
+	pub fn contains(&self, date: Date) -> bool {
+		self.start <= date && date < self.end
+	}
+}
+
+/// Work out when each phase starts and ends, and fill in any missing colors.
+///
+/// Phases missing a past color take the next one from `palette`, or from the default colors
+/// when it's empty. Phases missing a future color get a faded version of their past color,
+/// blended towards `background`.
+pub fn resolve(
+	entries: &[PhaseEntry],
+	bday: Date,
+	death: Date,
+	background: &Color,
+	palette: &[Color],
+) -> Result<Vec<Phase>> {
+	let at_age = |age: i16| bday.add_years(age).or_prev_valid();
+	let palette = if palette.is_empty() {
+		DEFAULT_COLORS
+			.iter()
+			.map(|color| csscolorparser::parse(color))
+			.collect::<Result<Vec<_>, _>>()?
+	} else {
+		palette.to_vec()
+	};
+	entries
+		.iter()
+		.zip(palette.iter().cycle())
+		.map(|(entry, default_color)| {
+			let start = match (entry.from, entry.from_age) {
+				(Some(_), Some(_)) => {
+					bail!(
+						"The phase \"{}\" can't have both `from` and `from_age`",
+						entry.name
+					)
+				},
+				(Some(date), None) => date,
+				(None, Some(age)) => at_age(age),
+				(None, None) => bday,
+			};
+			let end = match (entry.to, entry.to_age) {
+				(Some(_), Some(_)) => {
+					bail!(
+						"The phase \"{}\" can't have both `to` and `to_age`",
+						entry.name
+					)
+				},
+				(Some(date), None) => date.next(),
+				(None, Some(age)) => at_age(age),
+				(None, None) => death,
+			};
+			if end <= start {
+				bail!("The phase \"{}\" ends before it starts", entry.name);
+			}
+			let color_past = entry
+				.past_color
+				.clone()
+				.unwrap_or_else(|| default_color.clone());
+			let color_future = entry
+				.future_color
+				.clone()
+				.unwrap_or_else(|| color_past.interpolate_oklab(background, 0.7));
+			Ok(Phase {
+				name: entry.name.clone(),
+				start,
+				end,
+				color_past,
+				color_future,
+			})
+		})
+		.collect()
+}
+
+/// Find the phase a day belongs to. Earlier phases win when they overlap.
+pub fn find(phases: &[Phase], date: Date) -> Option<&Phase> {
+	phases.iter().find(|phase| phase.contains(date))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn date(year: i16, month: u8, day: u8) -> Date {
+		Date::new(year, month, day).unwrap()
+	}
+
+	#[test]
+	fn phases_can_use_ages_or_dates() {
+		let config: crate::config::Config = crate::config::parse_toml(
+			r##"
+			[[phases]]
+			name = "Childhood"
+			to_age = 12
+			past_color = "#ff0000"
+
+			[[phases]]
+			name = "Career"
+			from = 2022-09-01
+			to = "2065-08-31"
+			past_color = "navy"
+			future_color = "lightblue"
+			"##,
+		)
+		.unwrap();
+		let bday = date(2000, 2, 29);
+		let white = Color::new(1.0, 1.0, 1.0, 1.0);
+		let phases = resolve(&config.phases, bday, date(2100, 2, 28), &white, &[]).unwrap();
+		assert_eq!(phases[0].start, bday);
+		assert_eq!(phases[0].end, date(2012, 2, 29));
+		assert_eq!(phases[0].color_past.to_hex_string(), "#ff0000");
+		assert_ne!(phases[0].color_future.to_hex_string(), "#ff0000");
+		assert_eq!(phases[1].start, date(2022, 9, 1));
+		assert_eq!(phases[1].end, date(2065, 9, 1));
+		assert_eq!(phases[1].color_future.to_hex_string(), "#add8e6");
+		assert_eq!(
+			find(&phases, date(2030, 1, 1)).map(|phase| phase.name.as_str()),
+			Some("Career")
+		);
+		assert!(find(&phases, date(2015, 1, 1)).is_none());
+	}
+}
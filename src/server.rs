@@ -0,0 +1,477 @@
+use std::{
+	collections::hash_map::DefaultHasher,
+	hash::{Hash, Hasher},
+	panic::{self, AssertUnwindSafe},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches};
+use gregorian::Date;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{apply_config, calendar_image, config::Config, death_info, Cli, Commands, LifeInfo};
+
+/// Options that would let anyone who can reach the server read or write its files.
+const FORBIDDEN: [&str; 7] = [
+	"config",
+	"output",
+	"events",
+	"week_shape_file",
+	"font_file",
+	"background_image",
+	"theme",
+];
+
+/// The biggest scale factor the server draws at, so one request can't tie it up for long.
+const MAX_SCALE_FACTOR: u32 = 20;
+/// The longest lifespan the server draws, in years.
+const MAX_LIFESPAN_YEARS: u16 = 150;
+
+/// A response before it's sent.
+#[derive(Debug)]
+struct Reply {
+	status: u16,
+	content_type: &'static str,
+	/// Set for anything that can be cached until the day changes
+	etag: Option<String>,
+	/// Problems with how an image looks, sent as `Warning` headers
+	warnings: Vec<String>,
+	body: Vec<u8>,
+}
+
+impl Reply {
+	fn text(status: u16, text: impl Into<String>) -> Self {
+		Self {
+			status,
+			content_type: "text/plain; charset=utf-8",
+			etag: None,
+			warnings: Vec::new(),
+			body: text.into().into_bytes(),
+		}
+	}
+}
+
+/// Find the option a query parameter stands for, and which of `commands` it belongs to.
+///
+/// Parameters can be spelled like the command line options, with dashes, or like the config
+/// file, with underscores.
+fn find_arg<'a>(commands: &'a [Command], key: &str) -> Option<(usize, &'a Arg)> {
+	let long = key.replace('_', "-");
+	commands.iter().enumerate().find_map(|(index, command)| {
+		command
+			.get_arguments()
+			.find(|arg| {
+				if arg.is_positional() {
+					arg.get_id() == long.replace('-', "_").as_str()
+				} else {
+					arg.get_long() == Some(long.as_str())
+						|| arg
+							.get_all_aliases()
+							.is_some_and(|aliases| aliases.contains(&long.as_str()))
+				}
+			})
+			.map(|arg| (index, arg))
+	})
+}
+
+/// Turn a query string into the command line that asks for the same thing.
+///
+/// `subcommands` are the commands the path stands for, like `["img", "grid"]`.
+fn command_line(subcommands: &[&str], query: &str) -> Result<Vec<String>> {
+	// The commands the options can belong to, from the outermost in
+	let mut commands = vec![Cli::command()];
+	for name in subcommands {
+		let command = commands
+			.last()
+			.and_then(|command| command.find_subcommand(name))
+			.cloned()
+			.with_context(|| format!("There's no command called `{name}`"))?;
+		commands.push(command);
+	}
+
+	let mut sections = vec![Vec::new(); commands.len()];
+	for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+		let (index, arg) = find_arg(&commands, &key)
+			.with_context(|| format!("There's no option called `{key}`"))?;
+		let id = arg.get_id().as_str();
+		if FORBIDDEN.contains(&id) {
+			bail!("`{key}` can't be used over HTTP");
+		}
+		let section: &mut Vec<String> = &mut sections[index];
+		let long = arg.get_long().unwrap_or(id);
+		if arg.is_positional() {
+			section.push(value.into_owned());
+		} else if matches!(arg.get_action(), ArgAction::SetTrue) {
+			match value.as_ref() {
+				"" | "true" => section.push(format!("--{long}")),
+				"false" => {},
+				_ => bail!("`{key}` can only be true or false"),
+			}
+		} else {
+			section.push(format!("--{long}={value}"));
+		}
+	}
+
+	let mut sections = sections.into_iter();
+	let mut args = vec![env!("CARGO_PKG_NAME").to_string()];
+	args.extend(sections.next().unwrap_or_default());
+	for (name, section) in subcommands.iter().zip(sections) {
+		args.push((*name).to_string());
+		args.extend(section);
+	}
+	Ok(args)
+}
+
+/// Read the command line a request stands for, like `main` does.
+fn parse(subcommands: &[&str], query: &str) -> Result<(Cli, ArgMatches, LifeInfo)> {
+	let args = command_line(subcommands, query)?;
+	let matches = Cli::command()
+		.try_get_matches_from(args)
+		.map_err(|error| anyhow!("{}", error.render()))?;
+	let cli = Cli::from_arg_matches(&matches)?;
+	let birthday = cli
+		.birthday
+		.context("Ask with a `birthday`, like `?birthday=1990-01-01`")?;
+	if cli.lifespan_years > MAX_LIFESPAN_YEARS {
+		bail!("The lifespan can be at most {MAX_LIFESPAN_YEARS} years");
+	}
+	let life_info = LifeInfo {
+		birthday,
+		lifespan_years: cli.lifespan_years,
+	};
+	Ok((cli, matches, life_info))
+}
+
+fn info(query: &str, today: Date) -> Result<Reply> {
+	let (_, _, life_info) = parse(&["info"], query)?;
+	let stats = death_info::stats(life_info.birthday, life_info.lifespan_years, today)?;
+	Ok(Reply {
+		status: 200,
+		content_type: "application/json",
+		etag: None,
+		warnings: Vec::new(),
+		body: serde_json::to_vec_pretty(&stats)?,
+	})
+}
+
+fn image(drawing: &str, extension: &str, query: &str, config: &Config) -> Result<Reply> {
+	let (query, content_type) = match extension {
+		"svg" => (query.to_string(), "image/svg+xml"),
+		"html" => (format!("{query}&format=html"), "text/html; charset=utf-8"),
+		_ => bail!("Images can be `.svg` or `.html`"),
+	};
+	let (cli, matches, life_info) = parse(&["img", drawing], &query)?;
+	let Commands::Image {
+		drawing_type,
+		drawing_info,
+	} = cli.command
+	else {
+		bail!("Only images can be drawn");
+	};
+	let (_, image_matches) = matches
+		.subcommand()
+		.context("The image command has no options")?;
+	let (drawing_type, drawing_info) =
+		apply_config(config, drawing_type, drawing_info, image_matches)?;
+	if drawing_info.scale_factor > MAX_SCALE_FACTOR {
+		bail!("The scale factor can be at most {MAX_SCALE_FACTOR}");
+	}
+	let (body, warnings) =
+		calendar_image::render_calendar(&drawing_type, &drawing_info, &life_info, config)?;
+	Ok(Reply {
+		status: 200,
+		content_type,
+		etag: None,
+		warnings,
+		body,
+	})
+}
+
+/// A tag that stays the same for the same request until the day changes, when every calendar
+/// moves on.
+fn etag(url: &str, today: Date) -> String {
+	let mut hasher = DefaultHasher::new();
+	(env!("CARGO_PKG_VERSION"), url, today.to_string()).hash(&mut hasher);
+	format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Work out the reply to a request, without sending it.
+fn respond(method: &Method, url: &str, if_none_match: Option<&str>, config: &Config) -> Reply {
+	if !matches!(*method, Method::Get | Method::Head) {
+		return Reply::text(405, "Only GET and HEAD requests are supported");
+	}
+	let today = Date::today_utc();
+	let (path, query) = url.split_once('?').unwrap_or((url, ""));
+	let tag = etag(url, today);
+	if if_none_match.is_some_and(|tags| tags.split(',').any(|other| other.trim() == tag)) {
+		return Reply {
+			status: 304,
+			content_type: "text/plain; charset=utf-8",
+			etag: Some(tag),
+			warnings: Vec::new(),
+			body: Vec::new(),
+		};
+	}
+	let reply = match path.trim_start_matches('/').rsplit_once('.') {
+		Some(("info", "json")) => info(query, today),
+		Some((drawing, extension))
+			if Cli::command()
+				.find_subcommand("img")
+				.and_then(|image| image.find_subcommand(drawing))
+				.is_some() =>
+		{
+			image(drawing, extension, query, config)
+		},
+		_ => return Reply::text(404, format!("There's nothing at {path}")),
+	};
+	// Anything that stops a calendar from being drawn is a problem with what was asked for.
+	reply.map_or_else(
+		|error| Reply::text(400, format!("{error:#}")),
+		|reply| Reply {
+			etag: Some(tag),
+			..reply
+		},
+	)
+}
+
+/// A `Warning` header's value for some text, with 199 as the code for any other kind of warning.
+///
+/// The text is quoted, leaving out anything a header can't hold.
+fn warning_value(text: &str) -> String {
+	let mut value = String::from("199 - \"");
+	for c in text.chars() {
+		match c {
+			'"' | '\\' => {
+				value.push('\\');
+				value.push(c);
+			},
+			' ' | '\t' | '!'..='~' => value.push(c),
+			_ => {},
+		}
+	}
+	value.push('"');
+	value
+}
+
+fn handle(request: Request, config: &Config) -> Result<()> {
+	let if_none_match = request
+		.headers()
+		.iter()
+		.find(|header| header.field.equiv("If-None-Match"))
+		.map(|header| header.value.to_string());
+	// A bug in drawing one calendar shouldn't stop the server answering everyone else.
+	let reply = panic::catch_unwind(AssertUnwindSafe(|| {
+		respond(
+			request.method(),
+			request.url(),
+			if_none_match.as_deref(),
+			config,
+		)
+	}))
+	.unwrap_or_else(|_| Reply::text(500, "Something went wrong drawing this calendar"));
+	let header = |name: &str, value: &str| {
+		Header::from_bytes(name, value).map_err(|()| anyhow!("Could not make a {name} header"))
+	};
+	let mut response = Response::from_data(reply.body)
+		.with_status_code(reply.status)
+		.with_header(header("Content-Type", reply.content_type)?);
+	if let Some(ref tag) = reply.etag {
+		// Check back each time, since the answer changes from one day to the next.
+		response.add_header(header("ETag", tag)?);
+		response.add_header(header("Cache-Control", "no-cache")?);
+	}
+	for warning in &reply.warnings {
+		// A warning that still can't be a header is left out rather than losing the image.
+		if let Ok(warning) = header("Warning", &warning_value(warning)) {
+			response.add_header(warning);
+		}
+	}
+	Ok(request.respond(response)?)
+}
+
+fn serve(server: &Server, config: &Config) {
+	for request in server.incoming_requests() {
+		// A client hanging up early is their problem, not the server's.
+		let _ = handle(request, config);
+	}
+}
+
+/// Answer requests for calendars until the program is stopped.
+pub fn run(bind: &str, config: &Config) -> Result<()> {
+	let server =
+		Server::http(bind).map_err(|error| anyhow!("Could not listen on {bind}: {error}"))?;
+	#[allow(clippy::print_stderr)]
+	{
+		eprintln!("Drawing calendars on http://{}", server.server_addr());
+	}
+	serve(&server, config);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		io::{Read, Write},
+		net::TcpStream,
+		thread,
+	};
+
+	use super::*;
+
+	#[test]
+	fn queries_turn_into_command_lines() {
+		assert_eq!(
+			command_line(
+				&["img", "grid"],
+				"birthday=1990-01-01&shape=circle&compact&color_primary=%23336699&lifespan-years=80"
+			)
+			.unwrap(),
+			[
+				"death-calendar",
+				"1990-01-01",
+				"--lifespan-years=80",
+				"img",
+				"--compact",
+				"--color-primary=#336699",
+				"grid",
+				"--week-shape=circle",
+			]
+		);
+		assert!(command_line(&["img", "grid"], "output=/etc/passwd").is_err());
+		assert!(command_line(&["img", "grid"], "theme=../../tmp/secret").is_err());
+		assert!(command_line(&["img", "grid"], "shape-of-things=1").is_err());
+	}
+
+	#[test]
+	fn warnings_are_quoted_for_headers() {
+		assert_eq!(
+			warning_value("The \"past\" and\nthe \\ future — look alike"),
+			r#"199 - "The \"past\" andthe \\ future  look alike""#
+		);
+		assert!(Header::from_bytes("Warning", warning_value("ünïcode\r\n")).is_ok());
+	}
+
+	fn get(address: &str, path: &str, extra_headers: &str) -> (String, String) {
+		let mut stream = TcpStream::connect(address).unwrap();
+		write!(
+			stream,
+			"GET {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\n{extra_headers}\r\n"
+		)
+		.unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		let (head, mut body) = response.split_once("\r\n\r\n").unwrap();
+		if header(head, "Transfer-Encoding") != Some("chunked") {
+			return (head.to_string(), body.to_string());
+		}
+		let mut unchunked = String::new();
+		loop {
+			let (size, rest) = body.split_once("\r\n").unwrap();
+			let size = usize::from_str_radix(size, 16).unwrap();
+			if size == 0 {
+				return (head.to_string(), unchunked);
+			}
+			unchunked.push_str(&rest[..size]);
+			body = &rest[size + 2..];
+		}
+	}
+
+	fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+		head.lines().find_map(|line| {
+			let (field, value) = line.split_once(": ")?;
+			field.eq_ignore_ascii_case(name).then_some(value)
+		})
+	}
+
+	#[test]
+	fn calendars_are_served_over_http() {
+		let server = Server::http("127.0.0.1:0").unwrap();
+		let address = server.server_addr().to_string();
+		thread::spawn(move || serve(&server, &Config::default()));
+
+		let (head, body) = get(&address, "/grid.svg?birthday=1990-01-01&shape=circle", "");
+		assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+		assert_eq!(header(&head, "Content-Type"), Some("image/svg+xml"));
+		assert!(body.starts_with("<svg"));
+		assert!(body.contains("<circle"));
+
+		let tag = header(&head, "ETag").unwrap();
+		let (head, body) = get(
+			&address,
+			"/grid.svg?birthday=1990-01-01&shape=circle",
+			&format!("If-None-Match: {tag}\r\n"),
+		);
+		assert!(head.starts_with("HTTP/1.1 304"), "{head}");
+		assert!(body.is_empty());
+		let (head, _) = get(
+			&address,
+			"/grid.svg?birthday=1990-01-01&shape=square",
+			&format!("If-None-Match: {tag}\r\n"),
+		);
+		assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+
+		let (head, body) = get(&address, "/log.html?birthday=1990-01-01", "");
+		assert_eq!(
+			header(&head, "Content-Type"),
+			Some("text/html; charset=utf-8")
+		);
+		assert!(body.starts_with("<!DOCTYPE html>"));
+		assert!(header(&head, "Warning").is_none());
+
+		let (head, body) = get(
+			&address,
+			"/log.html?birthday=1990-01-01&color-primary=%23777777&color-secondary=%23888888",
+			"",
+		);
+		let warning = header(&head, "Warning").unwrap();
+		assert!(
+			warning.starts_with("199 - \"The primary and secondary colors have a contrast"),
+			"{warning}"
+		);
+		assert!(body.contains("<p class=\"warning\">Warning: The primary and secondary"));
+
+		let (head, body) = get(
+			&address,
+			"/info.json?birthday=2000-01-01&lifespan_years=80",
+			"",
+		);
+		assert_eq!(header(&head, "Content-Type"), Some("application/json"));
+		let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+		assert_eq!(stats["death_day"], "2080-01-01");
+		assert_eq!(stats["lifespan"]["years"], 80);
+
+		let (head, body) = get(&address, "/grid.svg", "");
+		assert!(head.starts_with("HTTP/1.1 400"), "{head}");
+		assert!(body.contains("birthday"));
+		let (head, _) = get(
+			&address,
+			"/grid.svg?birthday=1990-01-01&events=/etc/passwd",
+			"",
+		);
+		assert!(head.starts_with("HTTP/1.1 400"), "{head}");
+		let (head, _) = get(&address, "/spiral.svg?birthday=1990-01-01", "");
+		assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+	}
+
+	#[test]
+	fn huge_calendars_are_turned_down() {
+		let server = Server::http("127.0.0.1:0").unwrap();
+		let address = server.server_addr().to_string();
+		thread::spawn(move || serve(&server, &Config::default()));
+
+		for path in [
+			"/grid.svg?birthday=1990-01-01&scale-factor=4000000000",
+			"/log.svg?birthday=1990-01-01&scale-factor=4000000000",
+			"/hourglass.svg?birthday=1990-01-01&lifespan-years=65535",
+			"/grid.svg?birthday=1990-01-01&length=4000000000",
+		] {
+			let (head, body) = get(&address, path, "");
+			assert!(head.starts_with("HTTP/1.1 400"), "{path}: {head}");
+			assert!(!body.is_empty());
+		}
+		// The server is still there for the next request.
+		let (head, body) = get(&address, "/grid.svg?birthday=1990-01-01", "");
+		assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+		assert!(body.starts_with("<svg"));
+	}
+}
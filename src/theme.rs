@@ -0,0 +1,207 @@
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use csscolorparser::Color;
+use serde::Deserialize;
+use serde_with::serde_as;
+
+use crate::{color, config::CssColor, THEMES_DIR_PATH};
+
+/// Themes that come with the program, by name.
+static BUNDLED: [(&str, &str); 4] = [
+	("gruvbox", include_str!("../themes/gruvbox.toml")),
+	("nord", include_str!("../themes/nord.toml")),
+	("paper", include_str!("../themes/paper.toml")),
+	(
+		"solarized-dark",
+		include_str!("../themes/solarized-dark.toml"),
+	),
+];
+
+/// A palette for every part of a calendar, read from a theme file.
+///
+/// Colors left out of a theme are left to the usual defaults.
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+	/// Behind everything else
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	background: Option<Color>,
+	/// Time already lived
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	past: Option<Color>,
+	/// Time still to come
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	future: Option<Color>,
+	/// The present moment
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	present: Option<Color>,
+	/// Outlines and lines
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	stroke: Option<Color>,
+	/// Labels
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	text: Option<Color>,
+	/// Arrows that point things out
+	#[serde_as(as = "Option<CssColor>")]
+	#[serde(default)]
+	accent: Option<Color>,
+	/// Colors given to phases that don't pick their own, in order
+	#[serde_as(as = "Vec<CssColor>")]
+	#[serde(default)]
+	pub phases: Vec<Color>,
+}
+
+impl Theme {
+	/// The theme's colors as options for every kind of image, like `color_primary = "#88c0d0"`.
+	pub fn options(&self) -> toml::Table {
+		[
+			("color_background", &self.background),
+			("color_primary", &self.past),
+			("color_secondary", &self.future),
+			("color_present", &self.present),
+			("color_stroke", &self.stroke),
+			("color_text", &self.text),
+			("color_accent", &self.accent),
+		]
+		.into_iter()
+		.filter_map(|(option, color)| {
+			Some((option.to_owned(), color.as_ref()?.to_hex_string().into()))
+		})
+		.collect()
+	}
+
+	/// Colors in the theme that someone with deuteranopia would have trouble telling apart.
+	pub fn deuteranopia_problems(&self) -> Vec<String> {
+		let mut colors = vec![
+			("past".to_owned(), self.past.as_ref()),
+			("future".to_owned(), self.future.as_ref()),
+		];
+		colors.extend(
+			(1..)
+				.zip(&self.phases)
+				.map(|(number, color)| (format!("phase color {number}"), Some(color))),
+		);
+		let colors = colors
+			.into_iter()
+			.filter_map(|(name, color)| Some((name, color::deuteranopia(color?))))
+			.collect::<Vec<_>>();
+		let mut problems = Vec::new();
+		for (index, (first_name, first)) in colors.iter().enumerate() {
+			for (second_name, second) in &colors[index + 1..] {
+				if color::distance(first, second) < color::MIN_DISTANCE {
+					problems.push(format!(
+						"The theme's {first_name} and {second_name} look alike with deuteranopia"
+					));
+				}
+			}
+		}
+		problems
+	}
+}
+
+/// Find a theme by name, in the themes directory first and then among the bundled ones.
+///
+/// Names can only have letters, digits, `-` and `_`, so they can't reach outside the directory.
+pub fn load(name: &str) -> Result<Theme> {
+	if name.is_empty()
+		|| !name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+	{
+		bail!("Theme names can only have letters, digits, `-` and `_`, not `{name}`");
+	}
+	if let Some(dir) = THEMES_DIR_PATH.as_ref() {
+		let path = dir.join(format!("{name}.toml"));
+		if path.is_file() {
+			let content = fs::read_to_string(&path)
+				.with_context(|| format!("Could not read {}", path.display()))?;
+			return toml::from_str(&content)
+				.with_context(|| format!("Could not read a theme from {}", path.display()));
+		}
+	}
+	let Some((_, content)) = BUNDLED.iter().find(|(bundled, _)| *bundled == name) else {
+		let names = BUNDLED.map(|(name, _)| name).join(", ");
+		let dir = THEMES_DIR_PATH.as_ref().map_or_else(String::new, |dir| {
+			format!(", or put your own in {}", dir.display())
+		});
+		bail!("There's no theme called `{name}`. Try one of {names}{dir}");
+	};
+	Ok(toml::from_str(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use clap::{CommandFactory, FromArgMatches};
+
+	use super::*;
+
+	#[test]
+	fn bundled_themes_fill_in_every_color() {
+		for (name, _) in BUNDLED {
+			let theme = load(name).unwrap();
+			assert_eq!(theme.options().len(), 7, "{name}");
+			assert!(!theme.phases.is_empty(), "{name}");
+			assert_eq!(
+				theme.deuteranopia_problems(),
+				Vec::<String>::new(),
+				"{name}"
+			);
+		}
+		assert!(load("no-such-theme").is_err());
+	}
+
+	#[test]
+	fn theme_names_stay_in_the_themes_directory() {
+		for name in [
+			"../config",
+			"../../../../tmp/secret",
+			"nord/..",
+			"..",
+			"a\\b",
+			"",
+		] {
+			let error = load(name).unwrap_err().to_string();
+			assert!(
+				error.starts_with("Theme names can only have"),
+				"{name}: {error}"
+			);
+		}
+	}
+
+	#[test]
+	fn themes_fill_in_what_is_not_set_elsewhere() {
+		let config = crate::config::parse_toml("[image]\ncolor_stroke = \"white\"").unwrap();
+		let args = [
+			"death-calendar",
+			"1990-01-01",
+			"img",
+			"--theme=nord",
+			"--color-text=red",
+			"log",
+		];
+		let matches = crate::Cli::command().try_get_matches_from(args).unwrap();
+		let crate::Commands::Image {
+			drawing_type,
+			drawing_info,
+		} = crate::Cli::from_arg_matches(&matches).unwrap().command
+		else {
+			panic!("Expected an image");
+		};
+		let (_, matches) = matches.subcommand().unwrap();
+		let (_, drawing_info) =
+			crate::apply_config(&config, drawing_type, drawing_info, matches).unwrap();
+		let hex = |color: Option<Color>| color.unwrap().to_hex_string();
+		assert_eq!(hex(drawing_info.color_text), "#ff0000");
+		assert_eq!(hex(drawing_info.color_stroke), "#ffffff");
+		assert_eq!(hex(drawing_info.color_accent), "#bf616a");
+		assert_eq!(drawing_info.color_primary.to_hex_string(), "#88c0d0");
+	}
+}